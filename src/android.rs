@@ -0,0 +1,499 @@
+use crate::{Arch, BuildEnv, Error, Result, TargetTriple};
+use semver::{Version, VersionReq};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Environment variables checked for the Android API level, in order.
+///
+/// `ANDROID_PLATFORM` is what the NDK's CMake toolchain uses, and
+/// `CARGO_NDK_ANDROID_PLATFORM` is set by `cargo-ndk`.
+pub const ANDROID_API_LEVEL_VARS: &[&str] = &[
+    "ANDROID_PLATFORM",
+    "ANDROID_NDK_API_LEVEL",
+    "CARGO_NDK_ANDROID_PLATFORM",
+];
+
+/// Parses `24`, `android-24` or `android24`.
+pub fn parse_android_api_level(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let level = s
+        .strip_prefix("android-")
+        .or_else(|| s.strip_prefix("android"))
+        .unwrap_or(s);
+    level.parse().ok()
+}
+
+/// Reads the Android API level from the first of [`ANDROID_API_LEVEL_VARS`]
+/// that's set.
+pub fn try_android_api_level() -> Result<Option<u32>> {
+    BuildEnv::from_env().try_android_api_level()
+}
+
+pub fn android_api_level() -> Option<u32> {
+    try_android_api_level().unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Environment variables that point directly at an NDK, in the order they're
+/// checked.
+pub const ANDROID_NDK_VARS: &[&str] = &["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT", "NDK_HOME"];
+
+/// An Android NDK installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndroidNdk {
+    path: PathBuf,
+    version: Version,
+}
+
+impl AndroidNdk {
+    /// Loads the NDK at `path`, reading its version from `source.properties`.
+    pub fn from_path(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let properties = path.join("source.properties");
+        let contents = fs::read_to_string(&properties).map_err(|source| Error::Io {
+            path: properties.clone(),
+            source,
+        })?;
+        let version = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "Pkg.Revision")
+            .and_then(|(_, value)| Version::parse(value.trim()).ok())
+            .ok_or_else(|| Error::InvalidNdk {
+                path: path.clone(),
+                reason: "`source.properties` has no valid `Pkg.Revision`".into(),
+            })?;
+        Ok(Self { path, version })
+    }
+
+    /// Finds an NDK using [`ANDROID_NDK_VARS`], falling back to the newest one
+    /// in `$ANDROID_HOME/ndk`. Variables pointing at something that isn't a
+    /// usable NDK are skipped with a warning.
+    pub fn try_locate() -> Result<Option<Self>> {
+        Self::try_locate_from(&BuildEnv::from_env())
+    }
+
+    pub fn locate() -> Option<Self> {
+        Self::try_locate().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`AndroidNdk::try_locate`], but reading variables from `env`.
+    pub fn try_locate_from(env: &BuildEnv) -> Result<Option<Self>> {
+        let vars = locator_vars(env)?;
+        for name in ANDROID_NDK_VARS {
+            if let Some(ndk) = Self::candidate(env, name, vars.get(name).cloned())? {
+                return Ok(Some(ndk));
+            }
+        }
+        match vars.get("ANDROID_HOME") {
+            Some(sdk) => Ok(Self::usable_in(sdk)?
+                .into_iter()
+                .max_by(|a, b| a.version.cmp(&b.version))),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the newest NDK whose version matches `requirement` (e.g.
+    /// `>=25, <27`), considering both [`ANDROID_NDK_VARS`] and everything in
+    /// `$ANDROID_HOME/ndk`.
+    pub fn try_locate_matching(requirement: &VersionReq) -> Result<Self> {
+        Self::try_locate_matching_from(requirement, &BuildEnv::from_env())
+    }
+
+    pub fn locate_matching(requirement: &VersionReq) -> Self {
+        Self::try_locate_matching(requirement).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`AndroidNdk::try_locate_matching`], but reading variables from
+    /// `env`.
+    pub fn try_locate_matching_from(requirement: &VersionReq, env: &BuildEnv) -> Result<Self> {
+        let vars = locator_vars(env)?;
+        let mut candidates = Vec::new();
+        for name in ANDROID_NDK_VARS {
+            candidates.extend(Self::candidate(env, name, vars.get(name).cloned())?);
+        }
+        if let Some(sdk) = vars.get("ANDROID_HOME") {
+            candidates.extend(Self::usable_in(sdk)?);
+        }
+        // The same NDK is often reachable both through a variable and through
+        // `$ANDROID_HOME`, possibly spelled differently.
+        let mut seen = HashSet::new();
+        candidates
+            .retain(|ndk| seen.insert(fs::canonicalize(&ndk.path).unwrap_or(ndk.path.clone())));
+        candidates.sort_by(|a, b| a.version.cmp(&b.version));
+        match candidates
+            .iter()
+            .rposition(|ndk| requirement.matches(&ndk.version))
+        {
+            Some(index) => Ok(candidates.swap_remove(index)),
+            None => Err(Error::NoMatchingNdk {
+                requirement: requirement.clone(),
+                found: candidates
+                    .into_iter()
+                    .map(|ndk| (ndk.version, ndk.path))
+                    .collect(),
+            }),
+        }
+    }
+
+    // Loads the NDK `name` points at, if it's set. A stale variable shouldn't
+    // break builds that would otherwise find a good NDK, so anything unusable
+    // is skipped with a warning.
+    fn candidate(env: &BuildEnv, name: &str, path: Option<String>) -> Result<Option<Self>> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match Self::from_path(path).and_then(|ndk| ndk.prebuilt_dir().map(|_| ndk)) {
+            Ok(ndk) => Ok(Some(ndk)),
+            Err(err) => {
+                env.try_emit(|directives| {
+                    directives.warning(&format!("ignoring `{}`: {}", name, err))
+                })?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn usable_in(sdk: impl AsRef<Path>) -> Result<Vec<Self>> {
+        Ok(Self::installed_in(sdk)?
+            .into_iter()
+            .filter(|ndk| ndk.prebuilt_dir().is_ok())
+            .collect())
+    }
+
+    /// Every NDK installed side-by-side in `<sdk>/ndk`.
+    pub fn installed_in(sdk: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let dir = sdk.as_ref().join("ndk");
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => return Err(Error::Io { path: dir, source }),
+        };
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Self::from_path(entry.path()).ok())
+            .collect())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// `toolchains/llvm/prebuilt/<host>`, preferring the directory for the
+    /// host we're running on.
+    pub fn prebuilt_dir(&self) -> Result<PathBuf> {
+        let prebuilt = self.path.join("toolchains/llvm/prebuilt");
+        let host = prebuilt.join(HOST_TAG);
+        if host.is_dir() {
+            return Ok(host);
+        }
+        fs::read_dir(&prebuilt)
+            .map_err(|source| Error::Io {
+                path: prebuilt.clone(),
+                source,
+            })?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| path.is_dir())
+            .ok_or_else(|| Error::InvalidNdk {
+                path: self.path.clone(),
+                reason: "no prebuilt LLVM toolchain".into(),
+            })
+    }
+
+    pub fn sysroot(&self) -> Result<PathBuf> {
+        Ok(self.prebuilt_dir()?.join("sysroot"))
+    }
+
+    /// The headers for `triple` that clang won't find from `--sysroot` alone.
+    pub fn include_dirs(&self, triple: &TargetTriple) -> Result<Vec<PathBuf>> {
+        let include = self.sysroot()?.join("usr/include");
+        Ok(arch_include_dir(triple)
+            .map(|dir| include.join(dir))
+            .into_iter()
+            .collect())
+    }
+}
+
+// Reads the variables the locator looks at, emitting
+// `cargo:rerun-if-env-changed` for each of them.
+fn locator_vars(env: &BuildEnv) -> Result<HashMap<&'static str, String>> {
+    let mut vars = HashMap::new();
+    for &name in ANDROID_NDK_VARS.iter().chain(&["ANDROID_HOME"]) {
+        if let Some(value) = env.try_var(name)? {
+            vars.insert(name, value);
+        }
+    }
+    Ok(vars)
+}
+
+const HOST_TAG: &str = if cfg!(target_os = "macos") {
+    "darwin-x86_64"
+} else if cfg!(target_os = "windows") {
+    "windows-x86_64"
+} else {
+    "linux-x86_64"
+};
+
+// The NDK names these after its own triples, which don't always match rustc's.
+fn arch_include_dir(triple: &TargetTriple) -> Option<&'static str> {
+    match triple.arch {
+        Arch::Aarch64 => Some("aarch64-linux-android"),
+        Arch::Arm(_) | Arch::Thumb(_) => Some("arm-linux-androideabi"),
+        Arch::X86(_) => Some("i686-linux-android"),
+        Arch::X86_64 => Some("x86_64-linux-android"),
+        Arch::Riscv64(_) => Some("riscv64-linux-android"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::DirectiveSyntax;
+
+    // Lays out just enough of an NDK for the locator and `ClangArgs`.
+    pub(crate) fn fake_ndk(dir: &Path, version: &str) -> PathBuf {
+        let sysroot = dir
+            .join("toolchains/llvm/prebuilt")
+            .join(HOST_TAG)
+            .join("sysroot");
+        fs::create_dir_all(sysroot.join("usr/include/aarch64-linux-android")).unwrap();
+        fs::write(
+            dir.join("source.properties"),
+            format!("Pkg.Desc = Android NDK\nPkg.Revision = {}\n", version),
+        )
+        .unwrap();
+        dir.to_owned()
+    }
+
+    fn vars(pairs: &[(&str, &Path)]) -> BuildEnv {
+        BuildEnv::from_map(
+            pairs
+                .iter()
+                .map(|(name, path)| (name.to_string(), path.to_str().unwrap().to_string())),
+        )
+    }
+
+    #[test]
+    fn parses_api_levels() {
+        for (s, level) in [
+            ("24", Some(24)),
+            ("android-26", Some(26)),
+            ("android21", Some(21)),
+        ] {
+            assert_eq!(parse_android_api_level(s), level, "{}", s);
+        }
+        for s in ["", "android-", "latest", "-1"] {
+            assert_eq!(parse_android_api_level(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn reads_version_from_source_properties() {
+        let dir = tempfile::tempdir().unwrap();
+        let ndk = AndroidNdk::from_path(fake_ndk(dir.path(), "26.1.10909125")).unwrap();
+        assert_eq!(ndk.version(), &Version::new(26, 1, 10909125));
+        assert_eq!(ndk.path(), dir.path());
+    }
+
+    #[test]
+    fn rejects_dirs_that_arent_ndks() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            AndroidNdk::from_path(dir.path()),
+            Err(Error::Io { .. })
+        ));
+        fs::write(dir.path().join("source.properties"), "Pkg.Revision = r25\n").unwrap();
+        assert!(matches!(
+            AndroidNdk::from_path(dir.path()),
+            Err(Error::InvalidNdk { .. })
+        ));
+    }
+
+    #[test]
+    fn finds_sysroot_and_include_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let ndk = AndroidNdk::from_path(fake_ndk(dir.path(), "25.2.9519653")).unwrap();
+        let sysroot = dir
+            .path()
+            .join("toolchains/llvm/prebuilt")
+            .join(HOST_TAG)
+            .join("sysroot");
+        assert_eq!(ndk.sysroot().unwrap(), sysroot);
+        let triple = TargetTriple::parse("aarch64-linux-android").unwrap();
+        assert_eq!(
+            ndk.include_dirs(&triple).unwrap(),
+            [sysroot.join("usr/include/aarch64-linux-android")]
+        );
+    }
+
+    #[test]
+    fn env_vars_win_over_android_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = fake_ndk(&dir.path().join("home"), "25.2.9519653");
+        fake_ndk(&dir.path().join("sdk/ndk/26.1.10909125"), "26.1.10909125");
+        let sdk = dir.path().join("sdk");
+        let ndk = AndroidNdk::try_locate_from(&vars(&[
+            ("ANDROID_NDK_ROOT", &home),
+            ("ANDROID_HOME", &sdk),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(ndk.path(), home);
+    }
+
+    #[test]
+    fn picks_newest_in_android_home() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["25.2.9519653", "26.1.10909125", "23.1.7779620"] {
+            fake_ndk(&dir.path().join("ndk").join(version), version);
+        }
+        let ndk = AndroidNdk::try_locate_from(&vars(&[("ANDROID_HOME", dir.path())]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(ndk.version(), &Version::new(26, 1, 10909125));
+    }
+
+    #[test]
+    fn skips_stale_env_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let stale = dir.path().join("gone");
+        let no_toolchain = dir.path().join("no-toolchain");
+        fs::create_dir_all(&no_toolchain).unwrap();
+        fs::write(
+            no_toolchain.join("source.properties"),
+            "Pkg.Revision = 27.0.0\n",
+        )
+        .unwrap();
+        let sdk = dir.path().join("sdk");
+        fake_ndk(&sdk.join("ndk/25.2.9519653"), "25.2.9519653");
+        let env = vars(&[
+            ("ANDROID_NDK_HOME", &stale),
+            ("NDK_HOME", &no_toolchain),
+            ("ANDROID_HOME", &sdk),
+        ])
+        .syntax(DirectiveSyntax::DoubleColon);
+        let ndk = AndroidNdk::try_locate_from(&env).unwrap().unwrap();
+        assert_eq!(ndk.version(), &Version::new(25, 2, 9519653));
+        let warnings = env
+            .emitted()
+            .into_iter()
+            .filter(|line| line.starts_with("cargo::warning="))
+            .collect::<Vec<_>>();
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].starts_with("cargo::warning=ignoring `ANDROID_NDK_HOME`: "));
+        assert!(warnings[1].starts_with("cargo::warning=ignoring `NDK_HOME`: "));
+    }
+
+    #[test]
+    fn finds_nothing_without_env_vars() {
+        let env = vars(&[]);
+        assert_eq!(AndroidNdk::try_locate_from(&env).unwrap(), None);
+        assert_eq!(
+            env.rerun_vars(),
+            [
+                "ANDROID_NDK_HOME",
+                "ANDROID_NDK_ROOT",
+                "NDK_HOME",
+                "ANDROID_HOME"
+            ]
+        );
+    }
+
+    fn requirement(s: &str) -> VersionReq {
+        VersionReq::parse(s).unwrap()
+    }
+
+    #[test]
+    fn picks_newest_matching_ndk() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in [
+            "24.0.8215888",
+            "25.2.9519653",
+            "26.1.10909125",
+            "27.0.12077973",
+        ] {
+            fake_ndk(&dir.path().join("ndk").join(version), version);
+        }
+        let ndk = AndroidNdk::try_locate_matching_from(
+            &requirement(">=25, <27"),
+            &vars(&[("ANDROID_HOME", dir.path())]),
+        )
+        .unwrap();
+        assert_eq!(ndk.version(), &Version::new(26, 1, 10909125));
+    }
+
+    #[test]
+    fn considers_env_vars_alongside_android_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = fake_ndk(&dir.path().join("home"), "25.2.9519653");
+        fake_ndk(&dir.path().join("sdk/ndk/26.1.10909125"), "26.1.10909125");
+        let sdk = dir.path().join("sdk");
+        let ndk = AndroidNdk::try_locate_matching_from(
+            &requirement("^25"),
+            &vars(&[("ANDROID_NDK_HOME", &home), ("ANDROID_HOME", &sdk)]),
+        )
+        .unwrap();
+        assert_eq!(ndk.path(), home);
+    }
+
+    #[test]
+    fn lists_each_ndk_once_when_nothing_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir.path().join("sdk");
+        // The same NDK through the environment and `$ANDROID_HOME`, with a
+        // different copy of the same version that sorts between them.
+        fake_ndk(&sdk.join("ndk/25.2.9519653"), "25.2.9519653");
+        fake_ndk(&sdk.join("ndk/copy"), "25.2.9519653");
+        fake_ndk(&sdk.join("ndk/23.1.7779620"), "23.1.7779620");
+        let spelled_differently = sdk.join("ndk/../ndk/25.2.9519653");
+        let err = AndroidNdk::try_locate_matching_from(
+            &requirement(">=26"),
+            &vars(&[
+                ("ANDROID_NDK_HOME", &spelled_differently),
+                ("ANDROID_HOME", &sdk),
+            ]),
+        )
+        .unwrap_err();
+        let found = match &err {
+            Error::NoMatchingNdk { found, .. } => found,
+            err => panic!("unexpected error: {}", err),
+        };
+        assert_eq!(
+            found,
+            &[
+                (Version::new(23, 1, 7779620), sdk.join("ndk/23.1.7779620")),
+                (Version::new(25, 2, 9519653), spelled_differently.clone()),
+                (Version::new(25, 2, 9519653), sdk.join("ndk/copy")),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "no Android NDK matching `>=26` was found (found 23.1.7779620 at {:?}, \
+                 25.2.9519653 at {:?}, 25.2.9519653 at {:?})",
+                sdk.join("ndk/23.1.7779620").display(),
+                spelled_differently.display(),
+                sdk.join("ndk/copy").display(),
+            )
+        );
+    }
+
+    #[test]
+    fn reports_when_no_ndks_exist() {
+        let err =
+            AndroidNdk::try_locate_matching_from(&requirement("^25"), &vars(&[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no Android NDK matching `^25` was found (no NDKs were found at all)"
+        );
+    }
+}
@@ -0,0 +1,840 @@
+use crate::{Arch, BuildEnv, Error, Os, ProcessRunner, Result, TargetOs, TargetTriple, ToolRunner};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+};
+
+/// The SDKs `xcrun --sdk` knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppleSdk {
+    IPhoneOs,
+    IPhoneSimulator,
+    AppleTvOs,
+    AppleTvSimulator,
+    WatchOs,
+    WatchSimulator,
+    XrOs,
+    XrSimulator,
+    MacOsX,
+}
+
+impl AppleSdk {
+    pub fn for_target(os: &TargetOs) -> Option<Self> {
+        let simulator = os.is_simulator();
+        Some(match os {
+            TargetOs::MacOs(_) | TargetOs::MacCatalyst(_) => Self::MacOsX,
+            TargetOs::Ios(_) if simulator => Self::IPhoneSimulator,
+            TargetOs::Ios(_) => Self::IPhoneOs,
+            TargetOs::TvOs(_) if simulator => Self::AppleTvSimulator,
+            TargetOs::TvOs(_) => Self::AppleTvOs,
+            TargetOs::WatchOs(_) if simulator => Self::WatchSimulator,
+            TargetOs::WatchOs(_) => Self::WatchOs,
+            TargetOs::VisionOs(_) if simulator => Self::XrSimulator,
+            TargetOs::VisionOs(_) => Self::XrOs,
+            TargetOs::Android(..)
+            | TargetOs::Linux(_)
+            | TargetOs::Windows(_)
+            | TargetOs::Wasm(_) => return None,
+        })
+    }
+
+    pub fn from_triple(triple: &TargetTriple) -> Option<Self> {
+        Self::for_target(&TargetOs::from_triple(triple.clone())?)
+    }
+
+    /// The name passed to `xcrun --sdk`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::IPhoneOs => "iphoneos",
+            Self::IPhoneSimulator => "iphonesimulator",
+            Self::AppleTvOs => "appletvos",
+            Self::AppleTvSimulator => "appletvsimulator",
+            Self::WatchOs => "watchos",
+            Self::WatchSimulator => "watchsimulator",
+            Self::XrOs => "xros",
+            Self::XrSimulator => "xrsimulator",
+            Self::MacOsX => "macosx",
+        }
+    }
+
+    /// The start of the SDK's directory name, e.g. `iPhoneOS` for
+    /// `iPhoneOS17.2.sdk`.
+    pub fn dir_prefix(&self) -> &'static str {
+        match self {
+            Self::IPhoneOs => "iPhoneOS",
+            Self::IPhoneSimulator => "iPhoneSimulator",
+            Self::AppleTvOs => "AppleTVOS",
+            Self::AppleTvSimulator => "AppleTVSimulator",
+            Self::WatchOs => "WatchOS",
+            Self::WatchSimulator => "WatchSimulator",
+            Self::XrOs => "XROS",
+            Self::XrSimulator => "XRSimulator",
+            Self::MacOsX => "MacOSX",
+        }
+    }
+
+    /// Whether `path` looks like this SDK, going by its directory name.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(self.dir_prefix()))
+            .is_some_and(|rest| {
+                rest.strip_suffix(".sdk")
+                    .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+            })
+    }
+
+    pub fn is_simulator(&self) -> bool {
+        matches!(
+            self,
+            Self::IPhoneSimulator
+                | Self::AppleTvSimulator
+                | Self::WatchSimulator
+                | Self::XrSimulator
+        )
+    }
+}
+
+impl fmt::Display for AppleSdk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Intel simulator triples predate the `-sim` suffix, so for those the arch is
+// the only tell.
+pub(crate) fn is_simulator_triple(triple: &TargetTriple) -> bool {
+    triple.is_simulator()
+        || (matches!(triple.os, Os::Ios | Os::TvOs | Os::WatchOs)
+            && !triple.is_mac_catalyst()
+            && matches!(triple.arch, Arch::X86_64 | Arch::X86(_)))
+}
+
+pub fn sdk_path(target: &str) -> Option<String> {
+    try_sdk_path(target).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Overrides the SDK path for whatever the current target (`TARGET`) is.
+/// It's ignored when asking about any other target.
+pub const SDK_PATH_OVERRIDE_VAR: &str = "FFI_HELPERS_SDK_PATH";
+
+/// Finds the SDK path for `target`, or returns `Ok(None)` if `target` isn't an
+/// Apple platform.
+///
+/// [`SDK_PATH_OVERRIDE_VAR`] wins if it's set, followed by `SDKROOT` as long
+/// as it points at the right platform's SDK. Otherwise we ask `xcrun`, and
+/// remember the answer both for the rest of the process and in `OUT_DIR`, so
+/// incremental builds don't have to ask again.
+pub fn try_sdk_path(target: &str) -> Result<Option<String>> {
+    try_cached_sdk_path_with(target, &ProcessRunner, &BuildEnv::from_env())
+}
+
+/// Like [`try_sdk_path`], but running `xcrun` through `runner` and reading
+/// variables from `env`. Answers are cached per SDK and `DEVELOPER_DIR`, in
+/// this process and in `OUT_DIR`.
+pub fn try_cached_sdk_path_with(
+    target: &str,
+    runner: &dyn ToolRunner,
+    env: &BuildEnv,
+) -> Result<Option<String>> {
+    sdk_path_inner(target, runner, env, true)
+}
+
+/// Like [`try_sdk_path`], but running `xcrun` through `runner` and reading
+/// variables from `env`. Nothing is cached, since `runner` may not be talking
+/// to the real `xcrun`.
+pub fn try_sdk_path_with(
+    target: &str,
+    runner: &dyn ToolRunner,
+    env: &BuildEnv,
+) -> Result<Option<String>> {
+    sdk_path_inner(target, runner, env, false)
+}
+
+fn sdk_path_inner(
+    target: &str,
+    runner: &dyn ToolRunner,
+    env: &BuildEnv,
+    cache: bool,
+) -> Result<Option<String>> {
+    let sdk = match AppleSdk::from_triple(&TargetTriple::parse(target)?) {
+        Some(sdk) => sdk,
+        None => return Ok(None),
+    };
+
+    let override_path = env.try_var(SDK_PATH_OVERRIDE_VAR)?;
+    let sdkroot = env.try_var("SDKROOT")?;
+    let developer_dir = env.try_var("DEVELOPER_DIR")?.unwrap_or_default();
+    if let Some(path) = override_path {
+        // Build scripts also ask about other targets (e.g. the host), which
+        // the override says nothing about.
+        if env
+            .cargo_var("TARGET")?
+            .is_none_or(|current| current == target)
+        {
+            return Ok(Some(path));
+        }
+    }
+    if let Some(path) = sdkroot.map(PathBuf::from) {
+        // Xcode sets this for whatever it's building, which isn't necessarily
+        // the target we're building for.
+        if path.is_absolute() && sdk.matches_path(&path) {
+            return path_string(path).map(Some);
+        }
+    }
+
+    let xcrun = || -> Result<String> {
+        Ok(runner
+            .run("xcrun", &["--sdk", sdk.name(), "--show-sdk-path"])?
+            .trim()
+            .to_string())
+    };
+    if !cache {
+        return xcrun().map(Some);
+    }
+
+    let key = (sdk, developer_dir.clone());
+    let cache = SDK_PATH_CACHE.get_or_init(Default::default);
+    if let Some(path) = cache.lock().unwrap().get(&key) {
+        return Ok(Some(path.clone()));
+    }
+
+    let cache_file = env
+        .cargo_var("OUT_DIR")?
+        .map(|out_dir| Path::new(&out_dir).join(format!("ffi-helpers-sdk-{}", sdk.name())));
+    let cached = cache_file
+        .as_ref()
+        .and_then(|file| fs::read_to_string(file).ok())
+        .and_then(|contents| {
+            let (cached_developer_dir, path) = contents.split_once('\n')?;
+            (cached_developer_dir == developer_dir && Path::new(path).is_dir())
+                .then(|| path.to_string())
+        });
+    let path = match cached {
+        Some(path) => path,
+        None => {
+            let path = xcrun()?;
+            if let Some(file) = &cache_file {
+                // This is only a cache, so it's fine if we can't write it.
+                let _ = fs::write(file, format!("{}\n{}", developer_dir, path));
+            }
+            path
+        }
+    };
+    cache.lock().unwrap().insert(key, path.clone());
+    Ok(Some(path))
+}
+
+/// Everything we know about an installed SDK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppleSdkInfo {
+    pub sdk: AppleSdk,
+    pub path: PathBuf,
+    pub version: AppleVersion,
+    /// e.g. `.../Platforms/iPhoneOS.platform`
+    pub platform_path: Option<PathBuf>,
+    /// e.g. `21C52`
+    pub build_version: Option<String>,
+}
+
+impl AppleSdkInfo {
+    /// Queries the SDK for `target`, or returns `Ok(None)` if `target` isn't
+    /// an Apple platform.
+    pub fn try_query(target: &str) -> Result<Option<Self>> {
+        Self::query_inner(target, &ProcessRunner, &BuildEnv::from_env(), true)
+    }
+
+    pub fn query(target: &str) -> Option<Self> {
+        Self::try_query(target).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`AppleSdkInfo::try_query`], but running `xcrun` through `runner`
+    /// and reading variables from `env`.
+    pub fn try_query_with(
+        target: &str,
+        runner: &dyn ToolRunner,
+        env: &BuildEnv,
+    ) -> Result<Option<Self>> {
+        Self::query_inner(target, runner, env, false)
+    }
+
+    fn query_inner(
+        target: &str,
+        runner: &dyn ToolRunner,
+        env: &BuildEnv,
+        cache: bool,
+    ) -> Result<Option<Self>> {
+        let sdk = match AppleSdk::from_triple(&TargetTriple::parse(target)?) {
+            Some(sdk) => sdk,
+            None => return Ok(None),
+        };
+        let path = match sdk_path_inner(target, runner, env, cache)? {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(None),
+        };
+        let xcrun = |flag| -> Result<String> {
+            Ok(runner
+                .run("xcrun", &["--sdk", sdk.name(), flag])?
+                .trim()
+                .to_string())
+        };
+        let version = match xcrun("--show-sdk-version") {
+            Ok(version) => version.parse().map_err(|_| Error::ToolFailed {
+                command: format!("xcrun --sdk {} --show-sdk-version", sdk.name()),
+                code: None,
+                stderr: format!("unexpected output {:?}", version),
+            })?,
+            // Without `xcrun`, the SDK can still tell us its own version.
+            Err(err) => {
+                return Self::from_sdk_settings(sdk, path)
+                    .map(Some)
+                    .map_err(|_| err)
+            }
+        };
+        let platform_path = xcrun("--show-sdk-platform-path")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(|| infer_platform_path(&path));
+        Ok(Some(Self {
+            sdk,
+            path,
+            version,
+            platform_path,
+            build_version: xcrun("--show-sdk-build-version").ok(),
+        }))
+    }
+
+    /// Reads what we can from the SDK's own `SDKSettings.json`, for when
+    /// `xcrun` isn't available. The platform path is inferred from the SDK's
+    /// location, and the build version isn't available.
+    pub fn from_sdk_settings(sdk: AppleSdk, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let settings_path = path.join("SDKSettings.json");
+        let contents = fs::read_to_string(&settings_path).map_err(|source| Error::Io {
+            path: settings_path.clone(),
+            source,
+        })?;
+        let version = serde_json::from_str::<serde_json::Value>(&contents)
+            .ok()
+            .and_then(|settings| settings.get("Version")?.as_str()?.parse().ok())
+            .ok_or_else(|| Error::InvalidSdk {
+                path: path.clone(),
+                reason: "`SDKSettings.json` has no valid `Version`".into(),
+            })?;
+        let platform_path = infer_platform_path(&path);
+        Ok(Self {
+            sdk,
+            path,
+            version,
+            platform_path,
+            build_version: None,
+        })
+    }
+}
+
+// SDKs live at `<platform>.platform/Developer/SDKs/<sdk>.sdk`.
+fn infer_platform_path(sdk_path: &Path) -> Option<PathBuf> {
+    sdk_path
+        .ancestors()
+        .nth(3)
+        .filter(|platform| platform.extension().is_some_and(|ext| ext == "platform"))
+        .map(Path::to_path_buf)
+}
+
+static SDK_PATH_CACHE: OnceLock<Mutex<HashMap<(AppleSdk, String), String>>> = OnceLock::new();
+
+fn path_string(path: PathBuf) -> Result<String> {
+    path.into_os_string()
+        .into_string()
+        .map_err(|path| Error::InvalidPath(path.into()))
+}
+
+/// An Apple OS or SDK version, like `14.0` or `10.15.4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AppleVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl AppleVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for AppleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(String);
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid version {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for AppleVersion {
+    type Err = ParseVersionError;
+
+    /// Accepts `14`, `14.0` and `14.0.1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.into());
+        let mut parts = s.trim().split('.').map(|part| part.parse::<u32>());
+        let major = parts.next().ok_or_else(err)?.map_err(|_| err())?;
+        let minor = parts.next().transpose().map_err(|_| err())?.unwrap_or(0);
+        let patch = parts.next().transpose().map_err(|_| err())?.unwrap_or(0);
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(Self::new(major, minor, patch))
+    }
+}
+
+/// The environment variable rustc and Xcode use for `os`'s deployment target.
+pub fn deployment_target_var(os: &TargetOs) -> Option<&'static str> {
+    match os {
+        TargetOs::MacOs(_) => Some("MACOSX_DEPLOYMENT_TARGET"),
+        TargetOs::Ios(_) | TargetOs::MacCatalyst(_) => Some("IPHONEOS_DEPLOYMENT_TARGET"),
+        TargetOs::TvOs(_) => Some("TVOS_DEPLOYMENT_TARGET"),
+        TargetOs::WatchOs(_) => Some("WATCHOS_DEPLOYMENT_TARGET"),
+        TargetOs::VisionOs(_) => Some("XROS_DEPLOYMENT_TARGET"),
+        TargetOs::Android(..) | TargetOs::Linux(_) | TargetOs::Windows(_) | TargetOs::Wasm(_) => {
+            None
+        }
+    }
+}
+
+/// Reads the deployment target for `os` from the environment, emitting
+/// `cargo:rerun-if-env-changed` for the variable.
+pub fn try_deployment_target(os: &TargetOs) -> Result<Option<AppleVersion>> {
+    try_deployment_target_with(os, &BuildEnv::from_env())
+}
+
+/// Like [`try_deployment_target`], but reading from `env`.
+pub fn try_deployment_target_with(os: &TargetOs, env: &BuildEnv) -> Result<Option<AppleVersion>> {
+    let name = match deployment_target_var(os) {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    env.try_var(name)?
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| Error::invalid_env_var(name, value))
+        })
+        .transpose()
+}
+
+pub fn deployment_target(os: &TargetOs) -> Option<AppleVersion> {
+    try_deployment_target(os).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Inserts `version` after the OS in a clang triple, e.g. `arm64-apple-ios`
+/// becomes `arm64-apple-ios14.0`, and `arm64-apple-ios-simulator` becomes
+/// `arm64-apple-ios14.0-simulator`.
+pub fn versioned_clang_target(clang_target: &str, version: AppleVersion) -> String {
+    let mut parts = clang_target
+        .splitn(4, '-')
+        .map(String::from)
+        .collect::<Vec<_>>();
+    if let Some(os) = parts.get_mut(2) {
+        os.push_str(&version.to_string());
+    }
+    parts.join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScriptedRunner;
+
+    fn no_vars() -> BuildEnv {
+        BuildEnv::from_map([("", ""); 0])
+    }
+
+    #[test]
+    fn asks_xcrun_for_the_right_sdk() {
+        for (target, sdk) in [
+            ("aarch64-apple-ios", "iphoneos"),
+            ("aarch64-apple-ios-sim", "iphonesimulator"),
+            ("x86_64-apple-ios", "iphonesimulator"),
+            ("aarch64-apple-ios-macabi", "macosx"),
+            ("aarch64-apple-darwin", "macosx"),
+            ("x86_64-apple-tvos", "appletvsimulator"),
+            ("aarch64-apple-watchos", "watchos"),
+            ("aarch64-apple-visionos-sim", "xrsimulator"),
+        ] {
+            let command = format!("xcrun --sdk {} --show-sdk-path", sdk);
+            let runner = ScriptedRunner::new().stdout(&command, "/sdks/Some.sdk\n");
+            assert_eq!(
+                try_sdk_path_with(target, &runner, &no_vars()).unwrap(),
+                Some("/sdks/Some.sdk".into()),
+                "{}",
+                target
+            );
+            assert_eq!(runner.calls(), [command]);
+        }
+    }
+
+    #[test]
+    fn skips_non_apple_targets() {
+        let runner = ScriptedRunner::new();
+        for target in ["aarch64-linux-android", "x86_64-unknown-linux-gnu"] {
+            assert_eq!(
+                try_sdk_path_with(target, &runner, &no_vars()).unwrap(),
+                None
+            );
+            assert_eq!(
+                AppleSdkInfo::try_query_with(target, &runner, &no_vars()).unwrap(),
+                None
+            );
+        }
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn reports_xcrun_failures() {
+        let runner = ScriptedRunner::new().stderr(
+            "xcrun --sdk iphoneos --show-sdk-path",
+            "xcrun: error: SDK \"iphoneos\" cannot be located",
+        );
+        match try_sdk_path_with("aarch64-apple-ios", &runner, &no_vars()) {
+            Err(Error::ToolFailed { code, stderr, .. }) => {
+                assert_eq!(code, Some(1));
+                assert!(stderr.contains("cannot be located"), "{}", stderr);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn override_only_applies_to_the_current_target() {
+        let env = BuildEnv::from_map([
+            ("TARGET", "aarch64-apple-ios"),
+            (SDK_PATH_OVERRIDE_VAR, "/custom/iPhoneOS.sdk"),
+        ]);
+        let runner =
+            ScriptedRunner::new().stdout("xcrun --sdk macosx --show-sdk-path", "/sdks/MacOSX.sdk");
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap(),
+            Some("/custom/iPhoneOS.sdk".into())
+        );
+        assert!(runner.calls().is_empty());
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-darwin", &runner, &env).unwrap(),
+            Some("/sdks/MacOSX.sdk".into())
+        );
+        assert_eq!(runner.calls(), ["xcrun --sdk macosx --show-sdk-path"]);
+        assert_eq!(
+            env.read_vars(),
+            [SDK_PATH_OVERRIDE_VAR, "SDKROOT", "DEVELOPER_DIR", "TARGET"]
+        );
+    }
+
+    #[test]
+    fn override_applies_without_target() {
+        let env = BuildEnv::from_map([(SDK_PATH_OVERRIDE_VAR, "/custom/MacOSX.sdk")]);
+        let runner = ScriptedRunner::new();
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-darwin", &runner, &env).unwrap(),
+            Some("/custom/MacOSX.sdk".into())
+        );
+    }
+
+    #[test]
+    fn sdkroot_only_applies_to_its_own_platform() {
+        let sdkroot = "/Xcode.app/Platforms/iPhoneOS.platform/Developer/SDKs/iPhoneOS17.2.sdk";
+        let env = BuildEnv::from_map([("SDKROOT", sdkroot)]);
+        let runner = ScriptedRunner::new().stdout(
+            "xcrun --sdk iphonesimulator --show-sdk-path",
+            "/sdks/iPhoneSimulator.sdk",
+        );
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap(),
+            Some(sdkroot.into())
+        );
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-ios-sim", &runner, &env).unwrap(),
+            Some("/sdks/iPhoneSimulator.sdk".into())
+        );
+        assert_eq!(
+            runner.calls(),
+            ["xcrun --sdk iphonesimulator --show-sdk-path"]
+        );
+
+        let relative = BuildEnv::from_map([("SDKROOT", "iPhoneOS17.2.sdk")]);
+        let runner = ScriptedRunner::new()
+            .stdout("xcrun --sdk iphoneos --show-sdk-path", "/sdks/iPhoneOS.sdk");
+        assert_eq!(
+            try_sdk_path_with("aarch64-apple-ios", &runner, &relative).unwrap(),
+            Some("/sdks/iPhoneOS.sdk".into())
+        );
+    }
+
+    // The in-process cache is keyed on `DEVELOPER_DIR`, so each of these tests
+    // uses its own to stay out of the others' way.
+    fn cached_env(developer_dir: &str, out_dir: Option<&Path>) -> BuildEnv {
+        let mut vars = vec![("DEVELOPER_DIR", developer_dir.to_string())];
+        vars.extend(out_dir.map(|dir| ("OUT_DIR", dir.to_str().unwrap().to_string())));
+        BuildEnv::from_map(vars)
+    }
+
+    const IPHONEOS_PATH: &str = "xcrun --sdk iphoneos --show-sdk-path";
+
+    #[test]
+    fn caches_sdk_paths_in_process() {
+        let env = cached_env("/test/in-process", None);
+        let runner = ScriptedRunner::new().stdout(IPHONEOS_PATH, "/sdks/iPhoneOS.sdk");
+        for _ in 0..2 {
+            assert_eq!(
+                try_cached_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap(),
+                Some("/sdks/iPhoneOS.sdk".into())
+            );
+        }
+        assert_eq!(runner.calls(), [IPHONEOS_PATH]);
+        // Uncached lookups always ask.
+        try_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap();
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[test]
+    fn caches_sdk_paths_in_out_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir.path().join("iPhoneOS.sdk");
+        fs::create_dir(&sdk).unwrap();
+        let sdk = sdk.to_str().unwrap();
+        let cache_file = dir.path().join("ffi-helpers-sdk-iphoneos");
+
+        let runner = ScriptedRunner::new().stdout(IPHONEOS_PATH, sdk);
+        let env = cached_env("/test/out-dir-write", Some(dir.path()));
+        try_cached_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap();
+        assert_eq!(
+            fs::read_to_string(&cache_file).unwrap(),
+            format!("/test/out-dir-write\n{}", sdk)
+        );
+
+        // A fresh process (i.e. a new `DEVELOPER_DIR` key) with a good cache
+        // file doesn't need `xcrun`.
+        fs::write(&cache_file, format!("/test/out-dir-read\n{}", sdk)).unwrap();
+        let runner = ScriptedRunner::new();
+        let env = cached_env("/test/out-dir-read", Some(dir.path()));
+        assert_eq!(
+            try_cached_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap(),
+            Some(sdk.into())
+        );
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn ignores_stale_out_dir_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir.path().join("iPhoneOS.sdk");
+        fs::create_dir(&sdk).unwrap();
+        let sdk = sdk.to_str().unwrap();
+        let cache_file = dir.path().join("ffi-helpers-sdk-iphoneos");
+        for (developer_dir, contents) in [
+            // Written for a different Xcode.
+            (
+                "/test/stale-developer-dir",
+                format!("/other/Xcode.app\n{}", sdk),
+            ),
+            // Pointing at an SDK that's since been removed.
+            (
+                "/test/stale-missing-sdk",
+                "/test/stale-missing-sdk\n/gone/iPhoneOS.sdk".into(),
+            ),
+        ] {
+            fs::write(&cache_file, contents).unwrap();
+            let runner = ScriptedRunner::new().stdout(IPHONEOS_PATH, sdk);
+            let env = cached_env(developer_dir, Some(dir.path()));
+            assert_eq!(
+                try_cached_sdk_path_with("aarch64-apple-ios", &runner, &env).unwrap(),
+                Some(sdk.into())
+            );
+            assert_eq!(runner.calls(), [IPHONEOS_PATH], "{}", developer_dir);
+            assert_eq!(
+                fs::read_to_string(&cache_file).unwrap(),
+                format!("{}\n{}", developer_dir, sdk)
+            );
+        }
+    }
+
+    #[test]
+    fn queries_sdk_info_from_xcrun() {
+        let runner = ScriptedRunner::new()
+            .stdout(
+                "xcrun --sdk iphoneos --show-sdk-path",
+                "/X/Platforms/iPhoneOS.platform/Developer/SDKs/iPhoneOS17.2.sdk\n",
+            )
+            .stdout("xcrun --sdk iphoneos --show-sdk-version", "17.2\n")
+            .stdout(
+                "xcrun --sdk iphoneos --show-sdk-platform-path",
+                "/X/Platforms/iPhoneOS.platform\n",
+            )
+            .stdout("xcrun --sdk iphoneos --show-sdk-build-version", "21C52\n");
+        let info = AppleSdkInfo::try_query_with("aarch64-apple-ios", &runner, &no_vars())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            info,
+            AppleSdkInfo {
+                sdk: AppleSdk::IPhoneOs,
+                path: "/X/Platforms/iPhoneOS.platform/Developer/SDKs/iPhoneOS17.2.sdk".into(),
+                version: AppleVersion::new(17, 2, 0),
+                platform_path: Some("/X/Platforms/iPhoneOS.platform".into()),
+                build_version: Some("21C52".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn infers_what_xcrun_wont_say() {
+        let runner = ScriptedRunner::new()
+            .stdout(
+                "xcrun --sdk macosx --show-sdk-path",
+                "/X/Platforms/MacOSX.platform/Developer/SDKs/MacOSX14.2.sdk",
+            )
+            .stdout("xcrun --sdk macosx --show-sdk-version", "14.2");
+        let info = AppleSdkInfo::try_query_with("aarch64-apple-darwin", &runner, &no_vars())
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.version, AppleVersion::new(14, 2, 0));
+        assert_eq!(
+            info.platform_path,
+            Some("/X/Platforms/MacOSX.platform".into())
+        );
+        assert_eq!(info.build_version, None);
+
+        let runner = ScriptedRunner::new()
+            .stdout("xcrun --sdk macosx --show-sdk-path", "/X/MacOSX.sdk")
+            .stdout("xcrun --sdk macosx --show-sdk-version", "fourteen");
+        assert!(matches!(
+            AppleSdkInfo::try_query_with("aarch64-apple-darwin", &runner, &no_vars()),
+            Err(Error::ToolFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_sdk_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir
+            .path()
+            .join("iPhoneSimulator.platform/Developer/SDKs/iPhoneSimulator17.2.sdk");
+        fs::create_dir_all(&sdk).unwrap();
+        fs::write(sdk.join("SDKSettings.json"), r#"{"Version": "17.2"}"#).unwrap();
+        let env = BuildEnv::from_map([("SDKROOT", sdk.to_str().unwrap())]);
+        let runner = ScriptedRunner::new();
+        let info = AppleSdkInfo::try_query_with("aarch64-apple-ios-sim", &runner, &env)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.sdk, AppleSdk::IPhoneSimulator);
+        assert_eq!(info.path, sdk);
+        assert_eq!(info.version, AppleVersion::new(17, 2, 0));
+        assert_eq!(
+            info.platform_path,
+            Some(dir.path().join("iPhoneSimulator.platform"))
+        );
+        assert_eq!(info.build_version, None);
+
+        fs::write(sdk.join("SDKSettings.json"), "{}").unwrap();
+        assert!(matches!(
+            AppleSdkInfo::try_query_with("aarch64-apple-ios-sim", &runner, &env),
+            Err(Error::ToolFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn matches_sdk_dirs() {
+        let sdk = AppleSdk::IPhoneOs;
+        assert!(sdk.matches_path(Path::new("/a/iPhoneOS17.2.sdk")));
+        assert!(sdk.matches_path(Path::new("iPhoneOS.sdk")));
+        assert!(!sdk.matches_path(Path::new("/a/iPhoneSimulator17.2.sdk")));
+        assert!(!sdk.matches_path(Path::new("/a/iPhoneOS17.2")));
+    }
+
+    #[test]
+    fn parses_and_prints_versions() {
+        for (input, expected, display) in [
+            ("14", AppleVersion::new(14, 0, 0), "14.0"),
+            ("14.0", AppleVersion::new(14, 0, 0), "14.0"),
+            ("10.15.4", AppleVersion::new(10, 15, 4), "10.15.4"),
+            (" 17.2\n", AppleVersion::new(17, 2, 0), "17.2"),
+        ] {
+            assert_eq!(input.parse::<AppleVersion>(), Ok(expected));
+            assert_eq!(expected.to_string(), display);
+        }
+        for input in ["", "14.", "a.b", "1.2.3.4"] {
+            assert!(input.parse::<AppleVersion>().is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn reads_deployment_targets_through_env() {
+        let os = |triple| TargetOs::from_triple(TargetTriple::parse(triple).unwrap()).unwrap();
+        let env = BuildEnv::from_map([
+            ("IPHONEOS_DEPLOYMENT_TARGET", "14.0"),
+            ("MACOSX_DEPLOYMENT_TARGET", "eleven"),
+        ]);
+        for triple in ["aarch64-apple-ios-sim", "aarch64-apple-ios-macabi"] {
+            assert_eq!(
+                try_deployment_target_with(&os(triple), &env).unwrap(),
+                Some(AppleVersion::new(14, 0, 0))
+            );
+        }
+        assert_eq!(
+            try_deployment_target_with(&os("aarch64-apple-tvos"), &env).unwrap(),
+            None
+        );
+        assert_eq!(
+            try_deployment_target_with(&os("aarch64-linux-android"), &env).unwrap(),
+            None
+        );
+        assert!(matches!(
+            try_deployment_target_with(&os("aarch64-apple-darwin"), &env),
+            Err(Error::InvalidEnvVar { .. })
+        ));
+        assert_eq!(
+            env.rerun_vars(),
+            [
+                "IPHONEOS_DEPLOYMENT_TARGET",
+                "TVOS_DEPLOYMENT_TARGET",
+                "MACOSX_DEPLOYMENT_TARGET"
+            ]
+        );
+    }
+
+    #[test]
+    fn versions_clang_targets() {
+        let version = AppleVersion::new(14, 0, 0);
+        assert_eq!(
+            versioned_clang_target("arm64-apple-ios", version),
+            "arm64-apple-ios14.0"
+        );
+        assert_eq!(
+            versioned_clang_target("arm64-apple-ios-simulator", version),
+            "arm64-apple-ios14.0-simulator"
+        );
+        assert_eq!(
+            versioned_clang_target("arm64-apple-ios-macabi", version),
+            "arm64-apple-ios14.0-macabi"
+        );
+    }
+}
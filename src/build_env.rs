@@ -0,0 +1,494 @@
+use crate::{
+    parse_android_api_level, Arch, CargoDirectives, DirectiveSyntax, Env, Error, Os, Result,
+    TargetTriple, Vendor, ANDROID_API_LEVEL_VARS,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fmt,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Cargo's `PROFILE`, which is only ever `debug` or `release`; custom profiles
+/// report whichever of the two they inherit from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    Debug,
+    Release,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release",
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cargo's `OPT_LEVEL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    /// `s`
+    Size,
+    /// `z`
+    MinSize,
+}
+
+impl OptLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::O0 => "0",
+            Self::O1 => "1",
+            Self::O2 => "2",
+            Self::O3 => "3",
+            Self::Size => "s",
+            Self::MinSize => "z",
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The environment cargo gives build scripts, read one variable at a time.
+///
+/// Every variable read is recorded. Variables that cargo doesn't set itself
+/// also get `cargo:rerun-if-env-changed` the first time they're read.
+///
+/// Anything that takes a `BuildEnv` also emits its directives (warnings,
+/// `rerun-if-changed`, ...) through it, in its [`DirectiveSyntax`]. They're
+/// printed for the real environment, and only recorded (see
+/// [`BuildEnv::emitted`]) when the variables come from
+/// [`BuildEnv::from_map`], e.g. in tests.
+#[derive(Debug)]
+pub struct BuildEnv {
+    vars: Option<HashMap<String, String>>,
+    syntax: DirectiveSyntax,
+    read: RefCell<Vec<String>>,
+    rerun: RefCell<Vec<String>>,
+    emitted: RefCell<Vec<String>>,
+}
+
+impl BuildEnv {
+    pub fn from_env() -> Self {
+        Self {
+            vars: None,
+            syntax: DirectiveSyntax::default(),
+            read: RefCell::default(),
+            rerun: RefCell::default(),
+            emitted: RefCell::default(),
+        }
+    }
+
+    pub fn from_map(
+        vars: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        Self {
+            vars: Some(
+                vars.into_iter()
+                    .map(|(name, value)| (name.into(), value.into()))
+                    .collect(),
+            ),
+            syntax: DirectiveSyntax::default(),
+            read: RefCell::default(),
+            rerun: RefCell::default(),
+            emitted: RefCell::default(),
+        }
+    }
+
+    /// Defaults to [`DirectiveSyntax::SingleColon`].
+    pub fn syntax(mut self, syntax: DirectiveSyntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    /// The names of the variables read so far, in the order they were first
+    /// read.
+    pub fn read_vars(&self) -> Vec<String> {
+        self.read.borrow().clone()
+    }
+
+    /// The subset of [`BuildEnv::read_vars`] that gets
+    /// `cargo:rerun-if-env-changed`.
+    pub fn rerun_vars(&self) -> Vec<String> {
+        self.rerun.borrow().clone()
+    }
+
+    /// Every directive emitted so far, one line each (e.g.
+    /// `cargo:warning=...`).
+    pub fn emitted(&self) -> Vec<String> {
+        self.emitted.borrow().clone()
+    }
+
+    /// Emits whatever `write` writes, in this environment's syntax.
+    pub fn try_emit(
+        &self,
+        write: impl FnOnce(&mut CargoDirectives<Vec<u8>>) -> Result<()>,
+    ) -> Result<()> {
+        let mut directives = CargoDirectives::new(Vec::new()).syntax(self.syntax);
+        write(&mut directives)?;
+        let out = directives.into_inner();
+        if self.vars.is_none() {
+            io::stdout()
+                .write_all(&out)
+                .map_err(Error::WriteDirective)?;
+        }
+        self.emitted
+            .borrow_mut()
+            .extend(String::from_utf8_lossy(&out).lines().map(String::from));
+        Ok(())
+    }
+
+    pub fn try_target(&self) -> Result<TargetTriple> {
+        Ok(TargetTriple::parse(&self.required("TARGET")?)?)
+    }
+
+    pub fn target(&self) -> TargetTriple {
+        self.try_target().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_host(&self) -> Result<TargetTriple> {
+        Ok(TargetTriple::parse(&self.required("HOST")?)?)
+    }
+
+    pub fn host(&self) -> TargetTriple {
+        self.try_host().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// `CARGO_CFG_TARGET_OS`, which says [`Os::Android`] for Android and
+    /// [`Os::Darwin`] for macOS.
+    pub fn try_target_os(&self) -> Result<Os> {
+        Ok(Os::parse(&self.required("CARGO_CFG_TARGET_OS")?))
+    }
+
+    pub fn target_os(&self) -> Os {
+        self.try_target_os().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_target_arch(&self) -> Result<Arch> {
+        Ok(Arch::parse(&self.required("CARGO_CFG_TARGET_ARCH")?))
+    }
+
+    pub fn target_arch(&self) -> Arch {
+        self.try_target_arch()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// `CARGO_CFG_TARGET_ENV`, which cargo sets to an empty string for targets
+    /// without one.
+    pub fn try_target_env(&self) -> Result<Option<Env>> {
+        let value = self.required("CARGO_CFG_TARGET_ENV")?;
+        Ok(Some(value)
+            .filter(|value| !value.is_empty())
+            .map(|value| Env::parse(&value)))
+    }
+
+    pub fn target_env(&self) -> Option<Env> {
+        self.try_target_env()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_target_vendor(&self) -> Result<Vendor> {
+        Ok(Vendor::parse(&self.required("CARGO_CFG_TARGET_VENDOR")?))
+    }
+
+    pub fn target_vendor(&self) -> Vendor {
+        self.try_target_vendor()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_profile(&self) -> Result<Profile> {
+        let value = self.required("PROFILE")?;
+        match value.as_str() {
+            "debug" => Ok(Profile::Debug),
+            "release" => Ok(Profile::Release),
+            _ => Err(Error::invalid_env_var("PROFILE", value)),
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.try_profile().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_opt_level(&self) -> Result<OptLevel> {
+        let value = self.required("OPT_LEVEL")?;
+        match value.as_str() {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            "s" => Ok(OptLevel::Size),
+            "z" => Ok(OptLevel::MinSize),
+            _ => Err(Error::invalid_env_var("OPT_LEVEL", value)),
+        }
+    }
+
+    pub fn opt_level(&self) -> OptLevel {
+        self.try_opt_level().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Whether any debug info is being generated. `DEBUG` is `true` or `false`
+    /// on current cargo, but older versions passed the `debug` setting through
+    /// as-is (`0`, `limited`, `line-tables-only`, ...).
+    pub fn try_debug(&self) -> Result<bool> {
+        let value = self.required("DEBUG")?;
+        match value.as_str() {
+            "false" | "0" | "none" => Ok(false),
+            "true"
+            | "1"
+            | "2"
+            | "limited"
+            | "full"
+            | "line-tables-only"
+            | "line-directives-only" => Ok(true),
+            _ => Err(Error::invalid_env_var("DEBUG", value)),
+        }
+    }
+
+    pub fn debug(&self) -> bool {
+        self.try_debug().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_out_dir(&self) -> Result<PathBuf> {
+        self.required("OUT_DIR").map(PathBuf::from)
+    }
+
+    pub fn out_dir(&self) -> PathBuf {
+        self.try_out_dir().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Reads the Android API level from the first of
+    /// [`ANDROID_API_LEVEL_VARS`] that's set.
+    pub fn try_android_api_level(&self) -> Result<Option<u32>> {
+        for name in ANDROID_API_LEVEL_VARS {
+            if let Some(value) = self.try_var(name)? {
+                return parse_android_api_level(&value)
+                    .map(Some)
+                    .ok_or_else(|| Error::invalid_env_var(name, value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn android_api_level(&self) -> Option<u32> {
+        self.try_android_api_level()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Reads any other variable the build depends on, e.g. one pointing at a
+    /// toolchain. Unlike the variables cargo sets, these get
+    /// `cargo:rerun-if-env-changed`.
+    pub fn try_var(&self, name: &str) -> Result<Option<String>> {
+        self.read_var(name, true)
+    }
+
+    // Cargo reruns build scripts whenever the variables it sets change, and
+    // documents `rerun-if-env-changed` as having no effect on them. Emitting it
+    // anyway would only switch off cargo's default of rerunning when any file
+    // in the package changes.
+    pub(crate) fn cargo_var(&self, name: &str) -> Result<Option<String>> {
+        self.read_var(name, false)
+    }
+
+    fn read_var(&self, name: &str, rerun: bool) -> Result<Option<String>> {
+        if !self.read.borrow().iter().any(|read| read == name) {
+            self.read.borrow_mut().push(name.into());
+        }
+        if rerun && !self.rerun.borrow().iter().any(|read| read == name) {
+            self.try_emit(|directives| directives.rerun_if_env_changed(name))?;
+            self.rerun.borrow_mut().push(name.into());
+        }
+        match &self.vars {
+            Some(vars) => Ok(vars.get(name).cloned()),
+            None => match env::var(name) {
+                Ok(value) => Ok(Some(value)),
+                Err(env::VarError::NotPresent) => Ok(None),
+                Err(err) => Err(Error::env_var(name, err)),
+            },
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<String> {
+        self.cargo_var(name)?
+            .ok_or_else(|| Error::env_var(name, env::VarError::NotPresent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetOs;
+
+    fn cargo_env(target: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("TARGET", target.into()),
+            ("HOST", "x86_64-unknown-linux-gnu".into()),
+            ("CARGO_CFG_TARGET_OS", "android".into()),
+            ("CARGO_CFG_TARGET_ARCH", "aarch64".into()),
+            ("CARGO_CFG_TARGET_ENV", "".into()),
+            ("CARGO_CFG_TARGET_VENDOR", "unknown".into()),
+            ("PROFILE", "release".into()),
+            ("OPT_LEVEL", "3".into()),
+            ("DEBUG", "false".into()),
+            ("OUT_DIR", "/target/out".into()),
+        ]
+    }
+
+    #[test]
+    fn reads_typed_values() {
+        let env = BuildEnv::from_map(cargo_env("aarch64-linux-android"));
+        assert_eq!(env.target().as_str(), "aarch64-linux-android");
+        assert_eq!(env.host().as_str(), "x86_64-unknown-linux-gnu");
+        assert_eq!(env.target_os(), Os::Android);
+        assert_eq!(env.target_arch(), Arch::Aarch64);
+        assert_eq!(env.target_env(), None);
+        assert_eq!(env.target_vendor(), Vendor::Unknown);
+        assert_eq!(env.profile(), Profile::Release);
+        assert_eq!(env.opt_level(), OptLevel::O3);
+        assert!(!env.debug());
+        assert_eq!(env.out_dir(), PathBuf::from("/target/out"));
+    }
+
+    #[test]
+    fn reads_cfg_spellings() {
+        let env = BuildEnv::from_map([
+            ("CARGO_CFG_TARGET_OS", "macos"),
+            ("CARGO_CFG_TARGET_ARCH", "x86"),
+            ("CARGO_CFG_TARGET_ENV", "msvc"),
+            ("CARGO_CFG_TARGET_VENDOR", "apple"),
+        ]);
+        assert_eq!(env.target_os(), Os::Darwin);
+        assert_eq!(env.target_arch(), Arch::X86("x86".into()));
+        assert_eq!(env.target_env(), Some(Env::Msvc));
+        assert_eq!(env.target_vendor(), Vendor::Apple);
+    }
+
+    #[test]
+    fn reads_opt_levels_and_debug() {
+        for (value, level) in [
+            ("0", OptLevel::O0),
+            ("1", OptLevel::O1),
+            ("2", OptLevel::O2),
+            ("3", OptLevel::O3),
+            ("s", OptLevel::Size),
+            ("z", OptLevel::MinSize),
+        ] {
+            assert_eq!(
+                BuildEnv::from_map([("OPT_LEVEL", value)]).opt_level(),
+                level
+            );
+            assert_eq!(level.to_string(), value);
+        }
+        for (value, debug) in [
+            ("true", true),
+            ("2", true),
+            ("line-tables-only", true),
+            ("false", false),
+            ("0", false),
+            ("none", false),
+        ] {
+            assert_eq!(
+                BuildEnv::from_map([("DEBUG", value)]).debug(),
+                debug,
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let env = BuildEnv::from_map([("PROFILE", "bench"), ("OPT_LEVEL", "4"), ("DEBUG", "yes")]);
+        for result in [
+            env.try_profile().map(|_| ()),
+            env.try_opt_level().map(|_| ()),
+            env.try_debug().map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(Error::InvalidEnvVar { .. })));
+        }
+    }
+
+    #[test]
+    fn reports_missing_vars() {
+        let err = BuildEnv::from_map(Vec::<(String, String)>::new())
+            .try_out_dir()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to read environment variable `OUT_DIR`: environment variable not found"
+        );
+    }
+
+    #[test]
+    fn only_reruns_for_vars_cargo_doesnt_set() {
+        let mut vars = cargo_env("aarch64-linux-android");
+        vars.push(("ANDROID_NDK_API_LEVEL", "android-26".into()));
+        let env = BuildEnv::from_map(vars);
+        let os = TargetOs::try_detect_from(&env).unwrap().unwrap();
+        assert_eq!(os.android_api_level(), Some(26));
+        env.out_dir();
+        assert_eq!(
+            env.read_vars(),
+            [
+                "TARGET",
+                "ANDROID_PLATFORM",
+                "ANDROID_NDK_API_LEVEL",
+                "OUT_DIR"
+            ]
+        );
+        assert_eq!(
+            env.rerun_vars(),
+            ["ANDROID_PLATFORM", "ANDROID_NDK_API_LEVEL"]
+        );
+        assert_eq!(
+            env.emitted(),
+            [
+                "cargo:rerun-if-env-changed=ANDROID_PLATFORM",
+                "cargo:rerun-if-env-changed=ANDROID_NDK_API_LEVEL"
+            ]
+        );
+    }
+
+    #[test]
+    fn emits_in_the_chosen_syntax() {
+        let env = BuildEnv::from_map([("FOO_DIR", "/foo")]).syntax(DirectiveSyntax::DoubleColon);
+        env.try_var("FOO_DIR").unwrap();
+        env.try_var("FOO_DIR").unwrap();
+        env.try_emit(|directives| directives.warning("one\ntwo"))
+            .unwrap();
+        assert_eq!(
+            env.emitted(),
+            [
+                "cargo::rerun-if-env-changed=FOO_DIR",
+                "cargo::warning=one",
+                "cargo::warning=two"
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_targets() {
+        let env = BuildEnv::from_map(cargo_env("aarch64-apple-ios-sim"));
+        let os = TargetOs::try_detect_from(&env).unwrap().unwrap();
+        assert!(os.is_ios() && os.is_simulator());
+        assert!(env.rerun_vars().is_empty());
+
+        let env = BuildEnv::from_map(cargo_env("x86_64-unknown-freebsd"));
+        assert_eq!(TargetOs::try_detect_from(&env).unwrap(), None);
+    }
+}
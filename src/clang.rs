@@ -0,0 +1,998 @@
+use crate::{
+    clang_target, rerun::rerun_paths, try_cached_sdk_path_with, try_deployment_target_with,
+    try_emit_rerun_if_changed_with, try_sdk_path_with, versioned_clang_target, AndroidNdk,
+    AppleVersion, BuildEnv, Env, Error, LinkItem, LinkKind, ProcessRunner, RerunGranularity,
+    Result, TargetOs, TargetTriple, ToolRunner,
+};
+use semver::VersionReq;
+use std::{fmt, path::Path, str::FromStr, sync::Arc};
+
+/// A C++ language standard, as passed to clang's `-std=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CppStandard {
+    Cpp98,
+    Cpp03,
+    Cpp11,
+    Cpp14,
+    Cpp17,
+    Cpp20,
+    Cpp23,
+    Gnu98,
+    Gnu03,
+    Gnu11,
+    Gnu14,
+    Gnu17,
+    Gnu20,
+    Gnu23,
+}
+
+impl CppStandard {
+    pub const ALL: [Self; 14] = [
+        Self::Cpp98,
+        Self::Cpp03,
+        Self::Cpp11,
+        Self::Cpp14,
+        Self::Cpp17,
+        Self::Cpp20,
+        Self::Cpp23,
+        Self::Gnu98,
+        Self::Gnu03,
+        Self::Gnu11,
+        Self::Gnu14,
+        Self::Gnu17,
+        Self::Gnu20,
+        Self::Gnu23,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cpp98 => "c++98",
+            Self::Cpp03 => "c++03",
+            Self::Cpp11 => "c++11",
+            Self::Cpp14 => "c++14",
+            Self::Cpp17 => "c++17",
+            Self::Cpp20 => "c++20",
+            Self::Cpp23 => "c++23",
+            Self::Gnu98 => "gnu++98",
+            Self::Gnu03 => "gnu++03",
+            Self::Gnu11 => "gnu++11",
+            Self::Gnu14 => "gnu++14",
+            Self::Gnu17 => "gnu++17",
+            Self::Gnu20 => "gnu++20",
+            Self::Gnu23 => "gnu++23",
+        }
+    }
+
+    pub fn flag(&self) -> String {
+        format!("-std={}", self.name())
+    }
+
+    /// Whether GNU extensions are enabled.
+    pub fn is_gnu(&self) -> bool {
+        matches!(
+            self,
+            Self::Gnu98
+                | Self::Gnu03
+                | Self::Gnu11
+                | Self::Gnu14
+                | Self::Gnu17
+                | Self::Gnu20
+                | Self::Gnu23
+        )
+    }
+
+    /// The standard selected by the `cpp-*` features. Since features are
+    /// additive, the highest enabled one wins; with none enabled, this is C++17.
+    pub fn from_features() -> Self {
+        if cfg!(feature = "cpp-17") {
+            Self::Cpp17
+        } else if cfg!(feature = "cpp-14") {
+            Self::Cpp14
+        } else if cfg!(feature = "cpp-11") {
+            Self::Cpp11
+        } else {
+            Self::Cpp17
+        }
+    }
+}
+
+impl Default for CppStandard {
+    fn default() -> Self {
+        Self::from_features()
+    }
+}
+
+impl fmt::Display for CppStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStandardError(String);
+
+impl fmt::Display for ParseStandardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unrecognized language standard {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseStandardError {}
+
+impl FromStr for CppStandard {
+    type Err = ParseStandardError;
+
+    /// Accepts `c++17`, `gnu++17` and `-std=c++17` spellings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("-std=").unwrap_or(s);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|std| std.name() == name)
+            .ok_or_else(|| ParseStandardError(s.into()))
+    }
+}
+
+/// A C language standard, as passed to clang's `-std=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CStandard {
+    C89,
+    C99,
+    C11,
+    #[default]
+    C17,
+    C23,
+    Gnu89,
+    Gnu99,
+    Gnu11,
+    Gnu17,
+    Gnu23,
+}
+
+impl CStandard {
+    pub const ALL: [Self; 10] = [
+        Self::C89,
+        Self::C99,
+        Self::C11,
+        Self::C17,
+        Self::C23,
+        Self::Gnu89,
+        Self::Gnu99,
+        Self::Gnu11,
+        Self::Gnu17,
+        Self::Gnu23,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::C89 => "c89",
+            Self::C99 => "c99",
+            Self::C11 => "c11",
+            Self::C17 => "c17",
+            Self::C23 => "c23",
+            Self::Gnu89 => "gnu89",
+            Self::Gnu99 => "gnu99",
+            Self::Gnu11 => "gnu11",
+            Self::Gnu17 => "gnu17",
+            Self::Gnu23 => "gnu23",
+        }
+    }
+
+    pub fn flag(&self) -> String {
+        format!("-std={}", self.name())
+    }
+
+    /// Whether GNU extensions are enabled.
+    pub fn is_gnu(&self) -> bool {
+        matches!(
+            self,
+            Self::Gnu89 | Self::Gnu99 | Self::Gnu11 | Self::Gnu17 | Self::Gnu23
+        )
+    }
+}
+
+impl fmt::Display for CStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CStandard {
+    type Err = ParseStandardError;
+
+    /// Accepts `c11`, `gnu11` and `-std=c11` spellings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("-std=").unwrap_or(s);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|std| std.name() == name)
+            .ok_or_else(|| ParseStandardError(s.into()))
+    }
+}
+
+/// The language clang should parse headers as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    C,
+    #[default]
+    Cpp,
+    ObjC,
+    ObjCpp,
+}
+
+impl Language {
+    /// The name passed to clang's `-x`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Cpp => "c++",
+            Self::ObjC => "objective-c",
+            Self::ObjCpp => "objective-c++",
+        }
+    }
+
+    pub fn flag(&self) -> String {
+        format!("-x{}", self.name())
+    }
+
+    /// Whether this is C++ or Objective-C++, and thus uses a C++ standard
+    /// library.
+    pub fn is_cpp(&self) -> bool {
+        matches!(self, Self::Cpp | Self::ObjCpp)
+    }
+
+    pub fn is_objc(&self) -> bool {
+        matches!(self, Self::ObjC | Self::ObjCpp)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The C++ standard library to pass to clang's `-stdlib=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StdLib {
+    LibCxx,
+    LibStdCxx,
+    /// Don't pass `-stdlib` at all, leaving it up to clang.
+    None,
+}
+
+impl StdLib {
+    /// libc++ on Apple platforms, Android and Emscripten, and libstdc++ on
+    /// GNU/Linux and MinGW. Anything else is left up to clang.
+    pub fn default_for(os: &TargetOs) -> Self {
+        let env = &os.triple().env;
+        match os {
+            TargetOs::Ios(_)
+            | TargetOs::MacOs(_)
+            | TargetOs::MacCatalyst(_)
+            | TargetOs::TvOs(_)
+            | TargetOs::WatchOs(_)
+            | TargetOs::VisionOs(_)
+            | TargetOs::Android(..) => Self::LibCxx,
+            TargetOs::Wasm(_) if os.is_emscripten() => Self::LibCxx,
+            TargetOs::Linux(_) if matches!(env, Some(Env::Gnu(_))) => Self::LibStdCxx,
+            TargetOs::Windows(_) if matches!(env, Some(Env::Gnu(_))) => Self::LibStdCxx,
+            TargetOs::Windows(_) if env == &Some(Env::GnuLlvm) => Self::LibCxx,
+            TargetOs::Linux(_) | TargetOs::Windows(_) | TargetOs::Wasm(_) => Self::None,
+        }
+    }
+
+    pub fn flag(&self) -> Option<&'static str> {
+        match self {
+            Self::LibCxx => Some("-stdlib=libc++"),
+            Self::LibStdCxx => Some("-stdlib=libstdc++"),
+            Self::None => None,
+        }
+    }
+}
+
+type OsMatcher = fn(&TargetOs) -> bool;
+
+/// Builds the arguments to hand to clang (usually via bindgen's
+/// `clang_args`).
+///
+/// ```no_run
+/// let args = ffi_helpers::ClangArgs::new()
+///     .include("vendor/include")
+///     .define("NDEBUG", None::<&str>)
+///     .for_os(ffi_helpers::TargetOs::is_android, ["-DANDROID_STL=c++_shared"])
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClangArgs {
+    target: Option<TargetTriple>,
+    language: Language,
+    std: CppStandard,
+    c_std: CStandard,
+    stdlib: Option<StdLib>,
+    includes: Vec<String>,
+    framework_dirs: Vec<String>,
+    system_framework_dirs: Vec<String>,
+    frameworks: Vec<LinkItem>,
+    defines: Vec<(String, Option<String>)>,
+    sysroot: Option<String>,
+    android_api_level: Option<u32>,
+    deployment_target: Option<AppleVersion>,
+    android_ndk: Option<AndroidNdk>,
+    android_ndk_version: Option<VersionReq>,
+    runner: Option<Arc<dyn ToolRunner>>,
+    rerun: Option<RerunGranularity>,
+    os_args: Vec<(OsMatcher, Vec<String>)>,
+    args: Vec<String>,
+}
+
+impl ClangArgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the target, which otherwise comes from `TARGET`.
+    pub fn target(mut self, target: TargetTriple) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Defaults to [`Language::Cpp`].
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// The standard to use for C++ and Objective-C++.
+    pub fn std(mut self, std: CppStandard) -> Self {
+        self.std = std;
+        self
+    }
+
+    /// Overrides the C++ standard library, which otherwise depends on the
+    /// target (see [`StdLib::default_for`]).
+    pub fn stdlib(mut self, stdlib: StdLib) -> Self {
+        self.stdlib = Some(stdlib);
+        self
+    }
+
+    /// The standard to use for C and Objective-C.
+    pub fn c_std(mut self, c_std: CStandard) -> Self {
+        self.c_std = c_std;
+        self
+    }
+
+    pub fn include(mut self, dir: impl Into<String>) -> Self {
+        self.includes.push(dir.into());
+        self
+    }
+
+    pub fn includes(mut self, dirs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.includes.extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds `dir` to the framework search path with `-F`.
+    pub fn framework_dir(mut self, dir: impl Into<String>) -> Self {
+        self.framework_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds `dir` to the framework search path with `-iframework`, so that
+    /// warnings in its headers are suppressed like for `-isystem`.
+    pub fn system_framework_dir(mut self, dir: impl Into<String>) -> Self {
+        self.system_framework_dirs.push(dir.into());
+        self
+    }
+
+    /// Makes the headers of every framework in `items` visible, e.g. those
+    /// found by [`LinkScanner::discover`](crate::LinkScanner::discover).
+    /// System frameworks (see [`LinkItem::is_system`]) use `-iframework`.
+    pub fn frameworks_from(mut self, items: &[LinkItem]) -> Self {
+        self.frameworks.extend(
+            items
+                .iter()
+                .filter(|item| item.kind == LinkKind::Framework)
+                .cloned(),
+        );
+        self
+    }
+
+    pub fn define(mut self, name: impl Into<String>, value: Option<impl Into<String>>) -> Self {
+        self.defines.push((name.into(), value.map(Into::into)));
+        self
+    }
+
+    /// Overrides the sysroot. On Apple platforms, this otherwise comes from
+    /// `xcrun`.
+    pub fn sysroot(mut self, sysroot: impl Into<String>) -> Self {
+        self.sysroot = Some(sysroot.into());
+        self
+    }
+
+    /// Overrides the Android API level, which otherwise comes from the
+    /// environment (see [`ANDROID_API_LEVEL_VARS`](crate::ANDROID_API_LEVEL_VARS)).
+    /// It's appended to the clang target, which is what sets `__ANDROID_API__`.
+    pub fn android_api_level(mut self, api_level: u32) -> Self {
+        self.android_api_level = Some(api_level);
+        self
+    }
+
+    /// Overrides the Apple deployment target, which otherwise comes from the
+    /// environment (see [`deployment_target_var`](crate::deployment_target_var)).
+    /// It's added to the clang target, which is what `API_AVAILABLE` and
+    /// friends are checked against.
+    pub fn deployment_target(mut self, version: AppleVersion) -> Self {
+        self.deployment_target = Some(version);
+        self
+    }
+
+    /// Uses `ndk` for the Android sysroot and headers, rather than looking
+    /// for one with [`AndroidNdk::try_locate`].
+    pub fn android_ndk(mut self, ndk: AndroidNdk) -> Self {
+        self.android_ndk = Some(ndk);
+        self
+    }
+
+    /// Requires the located NDK to match `requirement`, picking the newest
+    /// one that does. Ignored if [`ClangArgs::android_ndk`] is used.
+    pub fn android_ndk_version(mut self, requirement: VersionReq) -> Self {
+        self.android_ndk_version = Some(requirement);
+        self
+    }
+
+    /// Runs external tools (i.e. `xcrun`) through `runner`.
+    pub fn runner(mut self, runner: Arc<dyn ToolRunner>) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Makes [`ClangArgs::build`] also emit `cargo:rerun-if-changed` for the
+    /// include and framework directories, or for every file inside them.
+    pub fn rerun_if_changed(mut self, granularity: RerunGranularity) -> Self {
+        self.rerun = Some(granularity);
+        self
+    }
+
+    /// Adds `args` only when building for an OS matching `matches`, e.g.
+    /// `TargetOs::is_android`.
+    pub fn for_os(
+        mut self,
+        matches: OsMatcher,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.os_args
+            .push((matches, args.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn build(&self) -> Vec<String> {
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_build(&self) -> Result<Vec<String>> {
+        self.try_build_with(&BuildEnv::from_env())
+    }
+
+    /// Like [`ClangArgs::try_build`], but reading whatever isn't set
+    /// explicitly (`TARGET`, the Android API level, the NDK, the deployment
+    /// target, ...) from `env`.
+    pub fn try_build_with(&self, env: &BuildEnv) -> Result<Vec<String>> {
+        let triple = match &self.target {
+            Some(triple) => triple.clone(),
+            None => env.try_target()?,
+        };
+        let os = TargetOs::from_triple(triple.clone())
+            .map(|os| match self.android_api_level {
+                Some(api_level) => Ok(os.with_android_api_level(api_level)),
+                None => os.try_with_android_api_level_from(env),
+            })
+            .transpose()?;
+
+        let mut args = vec![self.language.flag()];
+        if self.language.is_cpp() {
+            let stdlib = self
+                .stdlib
+                .or_else(|| os.as_ref().map(StdLib::default_for))
+                .unwrap_or(StdLib::None);
+            args.extend(stdlib.flag().map(Into::into));
+            args.push(self.std.flag());
+        } else {
+            args.push(self.c_std.flag());
+        }
+
+        let is_apple = os.as_ref().is_some_and(TargetOs::is_apple);
+        let is_android = os.as_ref().is_some_and(TargetOs::is_android);
+        let ndk = match &self.android_ndk {
+            Some(ndk) => Some(ndk.clone()),
+            None if is_android && self.sysroot.is_none() => match &self.android_ndk_version {
+                Some(requirement) => Some(AndroidNdk::try_locate_matching_from(requirement, env)?),
+                None => AndroidNdk::try_locate_from(env)?,
+            },
+            None => None,
+        }
+        .filter(|_| is_android);
+        let sysroot = match (&self.sysroot, &ndk) {
+            (Some(sysroot), _) => Some(sysroot.clone()),
+            (None, _) if is_apple => match &self.runner {
+                Some(runner) => try_sdk_path_with(triple.as_str(), runner.as_ref(), env)?,
+                None => try_cached_sdk_path_with(triple.as_str(), &ProcessRunner, env)?,
+            },
+            (None, Some(ndk)) => Some(path_to_string(&ndk.sysroot()?)?),
+            // Everything else uses the toolchain's own headers unless told
+            // otherwise.
+            (None, None) => None,
+        };
+        if let Some(sysroot) = sysroot {
+            if is_apple {
+                args.push("-isysroot".into());
+                args.push(sysroot);
+            } else {
+                args.push(format!("--sysroot={}", sysroot));
+            }
+        }
+        if let Some(ndk) = &ndk {
+            for dir in ndk.include_dirs(&triple)? {
+                args.push("-isystem".into());
+                args.push(path_to_string(&dir)?);
+            }
+        }
+
+        if let Some(os) = &os {
+            for (matches, os_args) in &self.os_args {
+                if matches(os) {
+                    args.extend(os_args.iter().cloned());
+                }
+            }
+        }
+
+        args.extend(self.defines.iter().map(|(name, value)| match value {
+            Some(value) => format!("-D{}={}", name, value),
+            None => format!("-D{}", name),
+        }));
+
+        args.extend(self.args.iter().cloned());
+
+        args.extend(self.includes.iter().map(|include| format!("-I{}", include)));
+        let mut framework_dirs = self.framework_dirs.clone();
+        let mut system_framework_dirs = self.system_framework_dirs.clone();
+        for item in &self.frameworks {
+            let dir = path_to_string(&item.search_path)?;
+            let dirs = if item.is_system() {
+                &mut system_framework_dirs
+            } else {
+                &mut framework_dirs
+            };
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        if let Some(granularity) = self.rerun {
+            let paths = self
+                .includes
+                .iter()
+                .chain(&framework_dirs)
+                .chain(&system_framework_dirs)
+                .flat_map(|dir| rerun_paths(Path::new(dir), granularity))
+                .collect::<Vec<_>>();
+            env.try_emit(|directives| try_emit_rerun_if_changed_with(&paths, env, directives))?;
+        }
+        args.extend(framework_dirs.iter().map(|dir| format!("-F{}", dir)));
+        for dir in system_framework_dirs {
+            args.push("-iframework".into());
+            args.push(dir);
+        }
+
+        let mut target = clang_target(triple.as_str());
+        if let Some(api_level) = os.as_ref().and_then(TargetOs::android_api_level) {
+            target.push_str(&api_level.to_string());
+        }
+        if let Some(os) = os.as_ref().filter(|os| os.is_apple()) {
+            let version = match self.deployment_target {
+                Some(version) => Some(version),
+                None => try_deployment_target_with(os, env)?,
+            };
+            if let Some(version) = version {
+                target = versioned_clang_target(&target, version);
+            }
+        }
+        args.push(format!("--target={}", target));
+        Ok(args)
+    }
+}
+
+fn path_to_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(Into::into)
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{android::tests::fake_ndk, Error, ScriptedRunner, TargetTriple};
+
+    fn triple(triple: &str) -> TargetTriple {
+        TargetTriple::parse(triple).unwrap()
+    }
+
+    fn build(args: ClangArgs, vars: &[(&str, &str)]) -> Vec<String> {
+        args.try_build_with(&BuildEnv::from_map(vars.iter().copied()))
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_standards() {
+        for std in CppStandard::ALL {
+            assert_eq!(std.name().parse(), Ok(std));
+            assert_eq!(std.flag().parse(), Ok(std));
+            assert_eq!(std.to_string(), std.name());
+        }
+        for std in CStandard::ALL {
+            assert_eq!(std.name().parse(), Ok(std));
+            assert_eq!(std.flag().parse(), Ok(std));
+            assert_eq!(std.to_string(), std.name());
+        }
+        assert!(CppStandard::Gnu17.is_gnu() && !CppStandard::Cpp17.is_gnu());
+        assert!(CStandard::Gnu11.is_gnu() && !CStandard::C11.is_gnu());
+        for s in ["c++2a", "c17", "std=c++17", ""] {
+            assert_eq!(
+                s.parse::<CppStandard>(),
+                Err(ParseStandardError(s.into())),
+                "{}",
+                s
+            );
+        }
+        assert!("c++17".parse::<CStandard>().is_err());
+    }
+
+    #[cfg(feature = "cpp-17")]
+    #[test]
+    fn picks_the_highest_enabled_standard() {
+        assert_eq!(CppStandard::from_features(), CppStandard::Cpp17);
+        assert_eq!(CppStandard::default(), CppStandard::Cpp17);
+    }
+
+    #[test]
+    fn picks_stdlib_per_target() {
+        for (target, stdlib) in [
+            ("aarch64-apple-ios", StdLib::LibCxx),
+            ("aarch64-apple-ios-macabi", StdLib::LibCxx),
+            ("x86_64-apple-darwin", StdLib::LibCxx),
+            ("aarch64-apple-visionos", StdLib::LibCxx),
+            ("aarch64-linux-android", StdLib::LibCxx),
+            ("wasm32-unknown-emscripten", StdLib::LibCxx),
+            ("x86_64-unknown-linux-gnu", StdLib::LibStdCxx),
+            ("x86_64-unknown-linux-musl", StdLib::None),
+            ("x86_64-pc-windows-gnu", StdLib::LibStdCxx),
+            ("aarch64-pc-windows-gnullvm", StdLib::LibCxx),
+            ("x86_64-pc-windows-msvc", StdLib::None),
+            ("wasm32-wasip1", StdLib::None),
+            ("wasm32-unknown-unknown", StdLib::None),
+        ] {
+            let os = TargetOs::from_triple(triple(target)).unwrap();
+            assert_eq!(StdLib::default_for(&os), stdlib, "{}", target);
+        }
+    }
+
+    #[test]
+    fn builds_args() {
+        let linux = || ClangArgs::new().target(triple("x86_64-unknown-linux-gnu"));
+        let ios_sim = || {
+            ClangArgs::new()
+                .target(triple("aarch64-apple-ios-sim"))
+                .sysroot("/sdk/iPhoneSimulator.sdk")
+        };
+        for (name, args, vars, expected) in [
+            (
+                "linux defaults",
+                linux(),
+                &[][..],
+                &[
+                    "-xc++",
+                    "-stdlib=libstdc++",
+                    "-std=c++17",
+                    "--target=x86_64-unknown-linux-gnu",
+                ][..],
+            ),
+            (
+                "target from env",
+                ClangArgs::new(),
+                &[("TARGET", "x86_64-pc-windows-msvc")],
+                &["-xc++", "-std=c++17", "--target=x86_64-pc-windows-msvc"],
+            ),
+            (
+                "stdlib override",
+                linux().stdlib(StdLib::LibCxx).std(CppStandard::Gnu20),
+                &[],
+                &[
+                    "-xc++",
+                    "-stdlib=libc++",
+                    "-std=gnu++20",
+                    "--target=x86_64-unknown-linux-gnu",
+                ],
+            ),
+            (
+                "no stdlib",
+                linux().stdlib(StdLib::None),
+                &[],
+                &["-xc++", "-std=c++17", "--target=x86_64-unknown-linux-gnu"],
+            ),
+            (
+                "c",
+                linux().language(Language::C).c_std(CStandard::Gnu11),
+                &[],
+                &["-xc", "-std=gnu11", "--target=x86_64-unknown-linux-gnu"],
+            ),
+            (
+                "objc",
+                ios_sim().language(Language::ObjC),
+                &[],
+                &[
+                    "-xobjective-c",
+                    "-std=c17",
+                    "-isysroot",
+                    "/sdk/iPhoneSimulator.sdk",
+                    "--target=arm64-apple-ios-simulator",
+                ],
+            ),
+            (
+                "objc++ with deployment target",
+                ios_sim()
+                    .language(Language::ObjCpp)
+                    .deployment_target(AppleVersion::new(14, 0, 0)),
+                &[("IPHONEOS_DEPLOYMENT_TARGET", "12.0")],
+                &[
+                    "-xobjective-c++",
+                    "-stdlib=libc++",
+                    "-std=c++17",
+                    "-isysroot",
+                    "/sdk/iPhoneSimulator.sdk",
+                    "--target=arm64-apple-ios14.0-simulator",
+                ],
+            ),
+            (
+                "deployment target from env",
+                ClangArgs::new()
+                    .target(triple("aarch64-apple-ios-macabi"))
+                    .sysroot("/sdk/MacOSX.sdk"),
+                &[("IPHONEOS_DEPLOYMENT_TARGET", "14.0")],
+                &[
+                    "-xc++",
+                    "-stdlib=libc++",
+                    "-std=c++17",
+                    "-isysroot",
+                    "/sdk/MacOSX.sdk",
+                    "--target=arm64-apple-ios14.0-macabi",
+                ],
+            ),
+            (
+                "android api level",
+                ClangArgs::new()
+                    .target(triple("armv7-linux-androideabi"))
+                    .sysroot("/ndk/sysroot")
+                    .android_api_level(24),
+                &[("ANDROID_PLATFORM", "android-21")],
+                &[
+                    "-xc++",
+                    "-stdlib=libc++",
+                    "-std=c++17",
+                    "--sysroot=/ndk/sysroot",
+                    "--target=armv7a-linux-androideabi24",
+                ],
+            ),
+            (
+                "android api level from env",
+                ClangArgs::new().sysroot("/ndk/sysroot"),
+                &[
+                    ("TARGET", "aarch64-linux-android"),
+                    ("ANDROID_PLATFORM", "android-26"),
+                ],
+                &[
+                    "-xc++",
+                    "-stdlib=libc++",
+                    "-std=c++17",
+                    "--sysroot=/ndk/sysroot",
+                    "--target=aarch64-linux-android26",
+                ],
+            ),
+            (
+                "everything else, in order",
+                linux()
+                    .for_os(TargetOs::is_linux, ["-DLINUX"])
+                    .for_os(TargetOs::is_android, ["-DANDROID"])
+                    .define("A", None::<&str>)
+                    .define("B", Some("1"))
+                    .arg("-Wall")
+                    .include("vendor/include")
+                    .framework_dir("vendor/Frameworks")
+                    .system_framework_dir("/Library/Frameworks"),
+                &[],
+                &[
+                    "-xc++",
+                    "-stdlib=libstdc++",
+                    "-std=c++17",
+                    "-DLINUX",
+                    "-DA",
+                    "-DB=1",
+                    "-Wall",
+                    "-Ivendor/include",
+                    "-Fvendor/Frameworks",
+                    "-iframework",
+                    "/Library/Frameworks",
+                    "--target=x86_64-unknown-linux-gnu",
+                ],
+            ),
+        ] {
+            assert_eq!(build(args, vars), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn reports_missing_target() {
+        assert!(matches!(
+            ClangArgs::new().try_build_with(&BuildEnv::from_map([("HOST", "x86_64-unknown-linux-gnu")])),
+            Err(Error::EnvVar { name, .. }) if name == "TARGET"
+        ));
+    }
+
+    #[test]
+    fn asks_xcrun_for_the_sysroot() {
+        let runner = ScriptedRunner::new().stdout(
+            "xcrun --sdk iphoneos --show-sdk-path",
+            "/sdks/iPhoneOS.sdk\n",
+        );
+        let args = ClangArgs::new()
+            .target(triple("aarch64-apple-ios"))
+            .runner(Arc::new(runner));
+        assert_eq!(
+            build(args, &[("IPHONEOS_DEPLOYMENT_TARGET", "15.0")]),
+            [
+                "-xc++",
+                "-stdlib=libc++",
+                "-std=c++17",
+                "-isysroot",
+                "/sdks/iPhoneOS.sdk",
+                "--target=arm64-apple-ios15.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn adds_framework_search_paths_once() {
+        let framework = |path: &str| LinkItem::new(LinkKind::Framework, "X", path).unwrap();
+        let items = [
+            framework("/vendor/Foo.framework"),
+            framework("/vendor/Bar.framework"),
+            framework("/other/Baz.framework"),
+            framework("/sdk/iPhoneOS17.2.sdk/System/Library/Frameworks/UIKit.framework"),
+            framework("/sdk/iPhoneOS17.2.sdk/System/Library/Frameworks/Metal.framework"),
+            LinkItem::new(LinkKind::Static, "foo", "/libs/libfoo.a").unwrap(),
+        ];
+        let args = ClangArgs::new()
+            .target(triple("aarch64-apple-ios"))
+            .sysroot("/sdk/iPhoneOS17.2.sdk")
+            .deployment_target(AppleVersion::new(17, 0, 0))
+            .framework_dir("/other")
+            .frameworks_from(&items);
+        assert_eq!(
+            build(args, &[]),
+            [
+                "-xc++",
+                "-stdlib=libc++",
+                "-std=c++17",
+                "-isysroot",
+                "/sdk/iPhoneOS17.2.sdk",
+                "-F/other",
+                "-F/vendor",
+                "-iframework",
+                "/sdk/iPhoneOS17.2.sdk/System/Library/Frameworks",
+                "--target=arm64-apple-ios17.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_the_ndk_sysroot_and_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let ndk_path = fake_ndk(&dir.path().join("ndk"), "26.1.10909125");
+        let sysroot = AndroidNdk::from_path(&ndk_path).unwrap().sysroot().unwrap();
+        let expected = [
+            "-xc++".to_string(),
+            "-stdlib=libc++".into(),
+            "-std=c++17".into(),
+            format!("--sysroot={}", sysroot.display()),
+            "-isystem".into(),
+            sysroot
+                .join("usr/include/aarch64-linux-android")
+                .to_str()
+                .unwrap()
+                .into(),
+            "--target=aarch64-linux-android24".into(),
+        ];
+        let android = || {
+            ClangArgs::new()
+                .target(triple("aarch64-linux-android"))
+                .android_api_level(24)
+        };
+        let ndk = AndroidNdk::from_path(&ndk_path).unwrap();
+        assert_eq!(build(android().android_ndk(ndk), &[]), expected);
+
+        // Located through the environment, skipping a stale variable.
+        let home = ndk_path.to_str().unwrap();
+        let gone = dir.path().join("gone");
+        let env = BuildEnv::from_map([
+            ("ANDROID_NDK_HOME", gone.to_str().unwrap()),
+            ("NDK_HOME", home),
+        ]);
+        assert_eq!(android().try_build_with(&env).unwrap(), expected);
+        assert!(env
+            .emitted()
+            .iter()
+            .any(|line| line.starts_with("cargo:warning=ignoring `ANDROID_NDK_HOME`")));
+        assert_eq!(
+            build(
+                android().android_ndk_version(VersionReq::parse("^26").unwrap()),
+                &[("NDK_HOME", home)]
+            ),
+            expected
+        );
+        assert!(matches!(
+            android()
+                .android_ndk_version(VersionReq::parse("^27").unwrap())
+                .try_build_with(&BuildEnv::from_map([("NDK_HOME", home)])),
+            Err(Error::NoMatchingNdk { .. })
+        ));
+
+        // Other targets never look for an NDK.
+        assert_eq!(
+            build(
+                ClangArgs::new().target(triple("x86_64-unknown-linux-musl")),
+                &[("NDK_HOME", home)]
+            ),
+            ["-xc++", "-std=c++17", "--target=x86_64-unknown-linux-musl"]
+        );
+    }
+
+    #[test]
+    fn reruns_for_include_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let include = dir.path().join("include");
+        std::fs::create_dir_all(include.join("foo")).unwrap();
+        std::fs::write(include.join("foo/foo.h"), "").unwrap();
+        let out_dir = dir.path().join("out");
+        let rerun_lines = |granularity| {
+            let env = BuildEnv::from_map([("OUT_DIR", out_dir.to_str().unwrap())]);
+            ClangArgs::new()
+                .target(triple("x86_64-unknown-linux-gnu"))
+                .include(include.to_str().unwrap())
+                .include(out_dir.to_str().unwrap())
+                .rerun_if_changed(granularity)
+                .try_build_with(&env)
+                .unwrap();
+            env.emitted()
+        };
+        assert_eq!(
+            rerun_lines(RerunGranularity::Directory),
+            [format!("cargo:rerun-if-changed={}", include.display())]
+        );
+        assert_eq!(
+            rerun_lines(RerunGranularity::File),
+            [format!(
+                "cargo:rerun-if-changed={}",
+                include.join("foo/foo.h").display()
+            )]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+/// rustc triples paired with the triple clang expects for them, covering all
+/// Tier 1 and Tier 2 targets along with the Tier 3 Apple targets.
+///
+/// These mostly match the `llvm-target` in rustc's target specs, except for
+/// Android, where we use the NDK's spelling so that an API level can be
+/// appended.
+pub const CLANG_TARGETS: &[(&str, &str)] = &[
+    // Tier 1
+    ("aarch64-apple-darwin", "arm64-apple-macosx"),
+    ("aarch64-pc-windows-msvc", "aarch64-pc-windows-msvc"),
+    ("aarch64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"),
+    ("i686-pc-windows-msvc", "i686-pc-windows-msvc"),
+    ("i686-unknown-linux-gnu", "i686-unknown-linux-gnu"),
+    ("x86_64-pc-windows-gnu", "x86_64-pc-windows-gnu"),
+    ("x86_64-pc-windows-msvc", "x86_64-pc-windows-msvc"),
+    ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+    // Tier 2 with host tools
+    ("aarch64-pc-windows-gnullvm", "aarch64-pc-windows-gnu"),
+    ("aarch64-unknown-linux-musl", "aarch64-unknown-linux-musl"),
+    ("aarch64-unknown-linux-ohos", "aarch64-unknown-linux-ohos"),
+    ("arm-unknown-linux-gnueabi", "arm-unknown-linux-gnueabi"),
+    ("arm-unknown-linux-gnueabihf", "arm-unknown-linux-gnueabihf"),
+    (
+        "armv7-unknown-linux-gnueabihf",
+        "armv7-unknown-linux-gnueabihf",
+    ),
+    ("armv7-unknown-linux-ohos", "armv7-unknown-linux-ohos"),
+    ("i686-pc-windows-gnu", "i686-pc-windows-gnu"),
+    (
+        "loongarch64-unknown-linux-gnu",
+        "loongarch64-unknown-linux-gnu",
+    ),
+    (
+        "loongarch64-unknown-linux-musl",
+        "loongarch64-unknown-linux-musl",
+    ),
+    ("powerpc-unknown-linux-gnu", "powerpc-unknown-linux-gnu"),
+    ("powerpc64-unknown-linux-gnu", "powerpc64-unknown-linux-gnu"),
+    (
+        "powerpc64le-unknown-linux-gnu",
+        "powerpc64le-unknown-linux-gnu",
+    ),
+    (
+        "powerpc64le-unknown-linux-musl",
+        "powerpc64le-unknown-linux-musl",
+    ),
+    ("riscv64gc-unknown-linux-gnu", "riscv64-unknown-linux-gnu"),
+    ("riscv64gc-unknown-linux-musl", "riscv64-unknown-linux-musl"),
+    ("s390x-unknown-linux-gnu", "s390x-unknown-linux-gnu"),
+    ("x86_64-apple-darwin", "x86_64-apple-macosx"),
+    ("x86_64-pc-windows-gnullvm", "x86_64-pc-windows-gnu"),
+    ("x86_64-unknown-freebsd", "x86_64-unknown-freebsd"),
+    ("x86_64-unknown-illumos", "x86_64-pc-solaris"),
+    ("x86_64-unknown-linux-musl", "x86_64-unknown-linux-musl"),
+    ("x86_64-unknown-linux-ohos", "x86_64-unknown-linux-ohos"),
+    ("x86_64-unknown-netbsd", "x86_64-unknown-netbsd"),
+    // Tier 2 without host tools
+    ("aarch64-apple-ios", "arm64-apple-ios"),
+    ("aarch64-apple-ios-macabi", "arm64-apple-ios-macabi"),
+    ("aarch64-apple-ios-sim", "arm64-apple-ios-simulator"),
+    ("aarch64-linux-android", "aarch64-linux-android"),
+    ("aarch64-unknown-fuchsia", "aarch64-unknown-fuchsia"),
+    ("aarch64-unknown-none", "aarch64-unknown-none"),
+    ("aarch64-unknown-none-softfloat", "aarch64-unknown-none"),
+    ("aarch64-unknown-uefi", "aarch64-unknown-windows"),
+    ("arm-linux-androideabi", "arm-linux-androideabi"),
+    ("arm-unknown-linux-musleabi", "arm-unknown-linux-musleabi"),
+    (
+        "arm-unknown-linux-musleabihf",
+        "arm-unknown-linux-musleabihf",
+    ),
+    ("arm64ec-pc-windows-msvc", "arm64ec-pc-windows-msvc"),
+    ("armebv7r-none-eabi", "armebv7r-none-eabi"),
+    ("armebv7r-none-eabihf", "armebv7r-none-eabihf"),
+    (
+        "armv5te-unknown-linux-gnueabi",
+        "armv5te-unknown-linux-gnueabi",
+    ),
+    (
+        "armv5te-unknown-linux-musleabi",
+        "armv5te-unknown-linux-musleabi",
+    ),
+    ("armv7-linux-androideabi", "armv7a-linux-androideabi"),
+    ("armv7-unknown-linux-gnueabi", "armv7-unknown-linux-gnueabi"),
+    (
+        "armv7-unknown-linux-musleabi",
+        "armv7-unknown-linux-musleabi",
+    ),
+    (
+        "armv7-unknown-linux-musleabihf",
+        "armv7-unknown-linux-musleabihf",
+    ),
+    ("armv7a-none-eabi", "armv7a-none-eabi"),
+    ("armv7r-none-eabi", "armv7r-none-eabi"),
+    ("armv7r-none-eabihf", "armv7r-none-eabihf"),
+    ("armv8r-none-eabihf", "armv8r-none-eabihf"),
+    ("i586-unknown-linux-gnu", "i586-unknown-linux-gnu"),
+    ("i586-unknown-linux-musl", "i586-unknown-linux-musl"),
+    ("i686-linux-android", "i686-linux-android"),
+    ("i686-unknown-freebsd", "i686-unknown-freebsd"),
+    ("i686-unknown-linux-musl", "i686-unknown-linux-musl"),
+    ("i686-unknown-uefi", "i686-unknown-windows-gnu"),
+    ("loongarch64-unknown-none", "loongarch64-unknown-none"),
+    (
+        "loongarch64-unknown-none-softfloat",
+        "loongarch64-unknown-none",
+    ),
+    ("nvptx64-nvidia-cuda", "nvptx64-nvidia-cuda"),
+    ("riscv32i-unknown-none-elf", "riscv32"),
+    ("riscv32im-unknown-none-elf", "riscv32"),
+    ("riscv32imac-unknown-none-elf", "riscv32"),
+    ("riscv32imafc-unknown-none-elf", "riscv32"),
+    ("riscv32imc-unknown-none-elf", "riscv32"),
+    ("riscv64gc-unknown-none-elf", "riscv64"),
+    ("riscv64imac-unknown-none-elf", "riscv64"),
+    ("sparc64-unknown-linux-gnu", "sparc64-unknown-linux-gnu"),
+    ("sparcv9-sun-solaris", "sparcv9-sun-solaris"),
+    ("thumbv6m-none-eabi", "thumbv6m-none-eabi"),
+    ("thumbv7em-none-eabi", "thumbv7em-none-eabi"),
+    ("thumbv7em-none-eabihf", "thumbv7em-none-eabihf"),
+    ("thumbv7m-none-eabi", "thumbv7m-none-eabi"),
+    ("thumbv7neon-linux-androideabi", "armv7a-linux-androideabi"),
+    (
+        "thumbv7neon-unknown-linux-gnueabihf",
+        "armv7-unknown-linux-gnueabihf",
+    ),
+    ("thumbv8m.base-none-eabi", "thumbv8m.base-none-eabi"),
+    ("thumbv8m.main-none-eabi", "thumbv8m.main-none-eabi"),
+    ("thumbv8m.main-none-eabihf", "thumbv8m.main-none-eabihf"),
+    ("wasm32-unknown-emscripten", "wasm32-unknown-emscripten"),
+    ("wasm32-unknown-unknown", "wasm32-unknown-unknown"),
+    ("wasm32-wasip1", "wasm32-wasip1"),
+    ("wasm32-wasip1-threads", "wasm32-wasi"),
+    ("wasm32-wasip2", "wasm32-wasip2"),
+    ("wasm32v1-none", "wasm32-unknown-unknown"),
+    ("x86_64-apple-ios", "x86_64-apple-ios-simulator"),
+    ("x86_64-apple-ios-macabi", "x86_64-apple-ios-macabi"),
+    ("x86_64-fortanix-unknown-sgx", "x86_64-elf"),
+    ("x86_64-linux-android", "x86_64-linux-android"),
+    ("x86_64-pc-solaris", "x86_64-pc-solaris"),
+    ("x86_64-unknown-fuchsia", "x86_64-unknown-fuchsia"),
+    ("x86_64-unknown-linux-gnux32", "x86_64-unknown-linux-gnux32"),
+    ("x86_64-unknown-none", "x86_64-unknown-none-elf"),
+    ("x86_64-unknown-redox", "x86_64-unknown-redox"),
+    ("x86_64-unknown-uefi", "x86_64-unknown-windows"),
+    // Tier 3 Apple targets
+    ("aarch64-apple-tvos", "arm64-apple-tvos"),
+    ("aarch64-apple-tvos-sim", "arm64-apple-tvos-simulator"),
+    ("aarch64-apple-visionos", "arm64-apple-xros"),
+    ("aarch64-apple-visionos-sim", "arm64-apple-xros-simulator"),
+    ("aarch64-apple-watchos", "arm64-apple-watchos"),
+    ("aarch64-apple-watchos-sim", "arm64-apple-watchos-simulator"),
+    ("arm64_32-apple-watchos", "arm64_32-apple-watchos"),
+    ("arm64e-apple-darwin", "arm64e-apple-macosx"),
+    ("arm64e-apple-ios", "arm64e-apple-ios"),
+    ("armv7k-apple-watchos", "armv7k-apple-watchos"),
+    ("armv7s-apple-ios", "armv7s-apple-ios"),
+    ("i386-apple-ios", "i386-apple-ios-simulator"),
+    ("x86_64-apple-tvos", "x86_64-apple-tvos-simulator"),
+    ("x86_64-apple-watchos-sim", "x86_64-apple-watchos-simulator"),
+    ("x86_64h-apple-darwin", "x86_64h-apple-macosx"),
+];
+
+/// Translates a rustc target triple into one clang understands. Triples that
+/// aren't in [`CLANG_TARGETS`] are passed through unchanged.
+///
+/// This covers <https://github.com/rust-lang/rust-bindgen/issues/1211>, among
+/// others.
+pub fn clang_target(rust_triple: &str) -> String {
+    CLANG_TARGETS
+        .iter()
+        .find(|(rust, _)| *rust == rust_triple)
+        .map_or(rust_triple, |(_, clang)| clang)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetTriple;
+    use std::collections::{HashMap, HashSet};
+
+    // `<rustc triple> <llvm-target>` for every target rustc knows about, from
+    // `rustc -Z unstable-options --print target-spec-json`.
+    const RUSTC_LLVM_TARGETS: &str = include_str!("../testdata/rustc-llvm-targets.txt");
+
+    // Where we deliberately use the NDK's spelling instead of rustc's.
+    const NDK_TARGETS: &[(&str, &str)] = &[
+        ("armv7-linux-androideabi", "armv7a-linux-androideabi"),
+        ("thumbv7neon-linux-androideabi", "armv7a-linux-androideabi"),
+    ];
+
+    #[test]
+    fn every_entry_matches_rustc() {
+        let llvm_targets = RUSTC_LLVM_TARGETS
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .collect::<HashMap<_, _>>();
+        for &(rust, clang) in CLANG_TARGETS {
+            assert!(TargetTriple::parse(rust).is_ok(), "{}", rust);
+            let expected = NDK_TARGETS
+                .iter()
+                .find(|(ndk_rust, _)| *ndk_rust == rust)
+                .map(|(_, ndk_clang)| *ndk_clang)
+                .or_else(|| llvm_targets.get(rust).copied())
+                .unwrap_or_else(|| panic!("{} isn't a rustc target", rust));
+            assert_eq!(clang, expected, "{}", rust);
+            assert_eq!(clang_target(rust), expected, "{}", rust);
+        }
+    }
+
+    #[test]
+    fn no_duplicate_entries() {
+        let mut seen = HashSet::new();
+        for (rust, _) in CLANG_TARGETS {
+            assert!(seen.insert(rust), "{} is listed twice", rust);
+        }
+    }
+
+    #[test]
+    fn named_cases() {
+        let cases = [
+            ("aarch64-apple-ios", "arm64-apple-ios"),
+            ("armv7-linux-androideabi", "armv7a-linux-androideabi"),
+            ("aarch64-apple-ios-sim", "arm64-apple-ios-simulator"),
+            ("x86_64-apple-ios-macabi", "x86_64-apple-ios-macabi"),
+            ("riscv64gc-unknown-linux-gnu", "riscv64-unknown-linux-gnu"),
+            ("riscv64gc-unknown-linux-musl", "riscv64-unknown-linux-musl"),
+            (
+                "thumbv7neon-unknown-linux-gnueabihf",
+                "armv7-unknown-linux-gnueabihf",
+            ),
+            ("thumbv7neon-linux-androideabi", "armv7a-linux-androideabi"),
+            ("wasm32-unknown-unknown", "wasm32-unknown-unknown"),
+            ("wasm32-wasip1-threads", "wasm32-wasi"),
+            ("wasm32v1-none", "wasm32-unknown-unknown"),
+        ];
+        for (rust, clang) in cases {
+            assert_eq!(clang_target(rust), clang, "{}", rust);
+        }
+    }
+
+    #[test]
+    fn passes_unknown_triples_through() {
+        for triple in ["x86_64-unknown-haiku", "made-up-triple", ""] {
+            assert_eq!(clang_target(triple), triple);
+        }
+    }
+}
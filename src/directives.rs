@@ -0,0 +1,224 @@
+use crate::{Error, Result};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+/// Which spelling of build script directives to write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirectiveSyntax {
+    /// `cargo:key=value`, which every version of cargo understands.
+    #[default]
+    SingleColon,
+    /// `cargo::key=value`, which needs cargo 1.77 or newer.
+    DoubleColon,
+}
+
+impl DirectiveSyntax {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::SingleColon => "cargo:",
+            Self::DoubleColon => "cargo::",
+        }
+    }
+}
+
+/// Writes build script directives (`cargo:rustc-link-lib=...` and friends),
+/// usually to stdout, where cargo picks them up.
+#[derive(Debug)]
+pub struct CargoDirectives<W = io::Stdout> {
+    out: W,
+    syntax: DirectiveSyntax,
+}
+
+impl CargoDirectives {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> CargoDirectives<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            syntax: DirectiveSyntax::default(),
+        }
+    }
+
+    /// Defaults to [`DirectiveSyntax::SingleColon`].
+    pub fn syntax(mut self, syntax: DirectiveSyntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// `rustc-link-lib`, e.g. `static=foo` or `framework=Foo`.
+    pub fn link_lib(&mut self, lib: &str) -> Result<()> {
+        self.directive("rustc-link-lib", lib)
+    }
+
+    /// `rustc-link-search`, e.g. `native=/path/to/dir`.
+    pub fn link_search(&mut self, search: &str) -> Result<()> {
+        self.directive("rustc-link-search", search)
+    }
+
+    pub fn link_arg(&mut self, arg: &str) -> Result<()> {
+        self.directive("rustc-link-arg", arg)
+    }
+
+    pub fn rerun_if_changed(&mut self, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        self.directive("rerun-if-changed", path)
+    }
+
+    pub fn rerun_if_env_changed(&mut self, name: &str) -> Result<()> {
+        self.directive("rerun-if-env-changed", name)
+    }
+
+    /// `rustc-cfg`, either as a bare `name` or as `name="value"`.
+    pub fn rustc_cfg(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        match value {
+            Some(value) => self.directive("rustc-cfg", &format!("{}={:?}", name, value)),
+            None => self.directive("rustc-cfg", name),
+        }
+    }
+
+    pub fn rustc_env(&mut self, name: &str, value: &str) -> Result<()> {
+        self.directive("rustc-env", &format!("{}={}", name, value))
+    }
+
+    /// Multi-line messages become one warning per line, since directives
+    /// can't span lines.
+    pub fn warning(&mut self, message: &str) -> Result<()> {
+        for line in message.lines() {
+            self.directive("warning", line)?;
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, key: &str, value: &str) -> Result<()> {
+        writeln!(self.out, "{}{}={}", self.syntax.prefix(), key, value)
+            .map_err(Error::WriteDirective)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(
+        syntax: DirectiveSyntax,
+        write: impl FnOnce(&mut CargoDirectives<Vec<u8>>) -> Result<()>,
+    ) -> String {
+        let mut directives = CargoDirectives::new(Vec::new()).syntax(syntax);
+        write(&mut directives).unwrap();
+        String::from_utf8(directives.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn writes_each_directive() {
+        for syntax in [DirectiveSyntax::SingleColon, DirectiveSyntax::DoubleColon] {
+            let out = written(syntax, |directives| {
+                directives.link_lib("static=foo")?;
+                directives.link_search("native=/path/to/lib")?;
+                directives.link_arg("-Wl,-rpath,/path/to/lib")?;
+                directives.rerun_if_changed(Path::new("include/foo.h"))?;
+                directives.rerun_if_env_changed("FOO_DIR")?;
+                directives.rustc_cfg("has_foo", None)?;
+                directives.rustc_cfg("foo_version", Some("1.2"))?;
+                directives.rustc_env("FOO_VERSION", "1.2")?;
+                directives.warning("foo is old")
+            });
+            let prefix = syntax.prefix();
+            assert_eq!(
+                out,
+                [
+                    "rustc-link-lib=static=foo",
+                    "rustc-link-search=native=/path/to/lib",
+                    "rustc-link-arg=-Wl,-rpath,/path/to/lib",
+                    "rerun-if-changed=include/foo.h",
+                    "rerun-if-env-changed=FOO_DIR",
+                    "rustc-cfg=has_foo",
+                    "rustc-cfg=foo_version=\"1.2\"",
+                    "rustc-env=FOO_VERSION=1.2",
+                    "warning=foo is old",
+                ]
+                .iter()
+                .map(|line| format!("{}{}\n", prefix, line))
+                .collect::<String>()
+            );
+        }
+    }
+
+    #[test]
+    fn defaults_to_single_colon() {
+        let mut directives = CargoDirectives::new(Vec::new());
+        directives.link_lib("foo").unwrap();
+        assert_eq!(directives.into_inner(), b"cargo:rustc-link-lib=foo\n");
+    }
+
+    #[test]
+    fn quotes_cfg_values() {
+        assert_eq!(
+            written(DirectiveSyntax::DoubleColon, |directives| {
+                directives.rustc_cfg("backend", Some(r#"say "hi" \ bye"#))
+            }),
+            "cargo::rustc-cfg=backend=\"say \\\"hi\\\" \\\\ bye\"\n"
+        );
+    }
+
+    #[test]
+    fn splits_multi_line_warnings() {
+        for syntax in [DirectiveSyntax::SingleColon, DirectiveSyntax::DoubleColon] {
+            assert_eq!(
+                written(syntax, |directives| {
+                    directives.warning("first\nsecond\r\nthird\n")
+                }),
+                format!(
+                    "{0}warning=first\n{0}warning=second\n{0}warning=third\n",
+                    syntax.prefix()
+                )
+            );
+        }
+        assert_eq!(
+            written(DirectiveSyntax::SingleColon, |directives| directives
+                .warning("")),
+            ""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_non_utf8_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let path = Path::new(OsStr::from_bytes(b"foo\xff.h"));
+        let mut directives = CargoDirectives::new(Vec::new());
+        assert!(matches!(
+            directives.rerun_if_changed(path),
+            Err(Error::InvalidPath(invalid)) if invalid == path
+        ));
+        assert!(directives.into_inner().is_empty());
+    }
+
+    #[test]
+    fn reports_write_errors() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(matches!(
+            CargoDirectives::new(Broken).link_lib("foo"),
+            Err(Error::WriteDirective(_))
+        ));
+    }
+}
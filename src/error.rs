@@ -0,0 +1,182 @@
+use crate::ParseTripleError;
+use semver::{Version, VersionReq};
+use std::{env, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// An environment variable we need wasn't set, or wasn't valid unicode.
+    EnvVar {
+        name: String,
+        source: env::VarError,
+    },
+    /// An environment variable was set, but we couldn't make sense of it.
+    InvalidEnvVar {
+        name: String,
+        value: String,
+    },
+    InvalidTriple(ParseTripleError),
+    /// An external tool (e.g. `xcrun`) couldn't be run or exited unsuccessfully.
+    ToolFailed {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// A path couldn't be represented as UTF-8, or was missing a file name.
+    InvalidPath(PathBuf),
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A directory we expected to be an Android NDK wasn't laid out like one.
+    InvalidNdk {
+        path: PathBuf,
+        reason: String,
+    },
+    /// An Apple SDK was missing information we needed.
+    InvalidSdk {
+        path: PathBuf,
+        reason: String,
+    },
+    /// An xcframework's `Info.plist` was missing or malformed, or it had no
+    /// slice for the target.
+    InvalidXcFramework {
+        path: PathBuf,
+        reason: String,
+    },
+    /// A glob pattern passed to [`LinkScanner`](crate::LinkScanner) couldn't
+    /// be parsed.
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+    /// A build script directive couldn't be written out.
+    WriteDirective(io::Error),
+    /// None of the NDKs we found satisfied the version requirement.
+    NoMatchingNdk {
+        requirement: VersionReq,
+        /// The version and location of each NDK we did find.
+        found: Vec<(Version, PathBuf)>,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn env_var(name: &str, source: env::VarError) -> Self {
+        Self::EnvVar {
+            name: name.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn invalid_env_var(name: &str, value: impl Into<String>) -> Self {
+        Self::InvalidEnvVar {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EnvVar { name, source } => {
+                write!(
+                    f,
+                    "failed to read environment variable `{}`: {}",
+                    name, source
+                )
+            }
+            Self::InvalidEnvVar { name, value } => {
+                write!(
+                    f,
+                    "environment variable `{}` has invalid value {:?}",
+                    name, value
+                )
+            }
+            Self::InvalidTriple(err) => err.fmt(f),
+            Self::ToolFailed {
+                command,
+                code,
+                stderr,
+            } => {
+                write!(f, "command {:?} failed", command)?;
+                if let Some(code) = code {
+                    write!(f, " with exit code {}", code)?;
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr.trim())?;
+                }
+                Ok(())
+            }
+            Self::InvalidPath(path) => write!(f, "invalid path {:?}", path),
+            Self::Io { path, source } => write!(f, "failed to read {:?}: {}", path, source),
+            Self::InvalidNdk { path, reason } => {
+                write!(f, "invalid Android NDK at {:?}: {}", path, reason)
+            }
+            Self::InvalidSdk { path, reason } => {
+                write!(f, "invalid Apple SDK at {:?}: {}", path, reason)
+            }
+            Self::InvalidXcFramework { path, reason } => {
+                write!(f, "invalid xcframework at {:?}: {}", path, reason)
+            }
+            Self::InvalidPattern { pattern, reason } => {
+                write!(f, "invalid glob pattern {:?}: {}", pattern, reason)
+            }
+            Self::WriteDirective(err) => write!(f, "failed to write cargo directive: {}", err),
+            Self::NoMatchingNdk { requirement, found } => {
+                write!(f, "no Android NDK matching `{}` was found", requirement)?;
+                if found.is_empty() {
+                    write!(f, " (no NDKs were found at all)")
+                } else {
+                    let found = found
+                        .iter()
+                        .map(|(version, path)| format!("{} at {:?}", version, path.display()))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    write!(f, " (found {})", found)
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::EnvVar { source, .. } => Some(source),
+            Self::InvalidTriple(err) => Some(err),
+            Self::Io { source, .. } => Some(source),
+            Self::WriteDirective(err) => Some(err),
+            Self::InvalidEnvVar { .. }
+            | Self::ToolFailed { .. }
+            | Self::InvalidPath(_)
+            | Self::InvalidNdk { .. }
+            | Self::InvalidSdk { .. }
+            | Self::InvalidXcFramework { .. }
+            | Self::InvalidPattern { .. }
+            | Self::NoMatchingNdk { .. } => None,
+        }
+    }
+}
+
+impl From<ParseTripleError> for Error {
+    fn from(err: ParseTripleError) -> Self {
+        Self::InvalidTriple(err)
+    }
+}
+
+impl From<bossy::Error> for Error {
+    fn from(err: bossy::Error) -> Self {
+        Self::ToolFailed {
+            command: err.command().into(),
+            code: err.code(),
+            // Spawn failures don't have any output, so the error itself is the
+            // most useful thing we can report.
+            stderr: err
+                .stderr()
+                .map(|stderr| String::from_utf8_lossy(stderr).into_owned())
+                .unwrap_or_else(|| err.to_string()),
+        }
+    }
+}
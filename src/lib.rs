@@ -1,74 +1,38 @@
-use std::path::Path;
+mod android;
+mod apple;
+mod build_env;
+mod clang;
+mod clang_target;
+mod directives;
+mod error;
+mod link;
+mod rerun;
+mod runner;
+mod target_os;
+mod triple;
+mod xcframework;
 
-#[cfg(feature = "cpp-11")]
-const CPP_VERSION: &str = "-std=c++11";
-#[cfg(feature = "cpp-14")]
-const CPP_VERSION: &str = "-std=c++14";
-#[cfg(feature = "cpp-17")]
-const CPP_VERSION: &str = "-std=c++17";
+pub use self::{
+    android::*, apple::*, build_env::*, clang::*, clang_target::*, directives::*, error::*,
+    link::*, rerun::*, runner::*, target_os::*, triple::*, xcframework::*,
+};
+pub use semver;
+use std::path::Path;
 
-pub fn target() -> String {
-    std::env::var("TARGET").unwrap()
+pub fn try_target() -> Result<String> {
+    std::env::var("TARGET").map_err(|err| Error::env_var("TARGET", err))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum TargetOs {
-    Ios(String),
-    Android(String),
-    MacOs(String),
+pub fn target() -> String {
+    try_target().unwrap_or_else(|err| panic!("{}", err))
 }
 
-impl TargetOs {
-    pub fn detect() -> Option<Self> {
-        let target = target();
-        if target.contains("ios") {
-            Some(Self::Ios(target))
-        } else if target.contains("apple") {
-            Some(Self::MacOs(target))
-        } else if target.contains("android") {
-            Some(Self::Android(target))
-        } else {
-            None
-        }
-    }
-
-    pub fn is_ios(&self) -> bool {
-        match self {
-            TargetOs::Ios(_) => true,
-            _ => false,
-        }
-    }
-    pub fn is_android(&self) -> bool {
-        match self {
-            TargetOs::Android(_) => true,
-            _ => false,
-        }
-    }
-    pub fn is_macos(&self) -> bool {
-        match self {
-            TargetOs::MacOs(_) => true,
-            _ => false,
-        }
-    }
+pub fn try_target_triple() -> Result<TargetTriple> {
+    Ok(TargetTriple::parse(&try_target()?)?)
 }
 
-pub fn sdk_path(target: &str) -> Option<String> {
-    let sdk = if target.contains("apple-darwin") {
-        "macosx"
-    } else if target == "x86_64-apple-ios" || target == "i386-apple-ios" {
-        "iphonesimulator"
-    } else if target == "aarch64-apple-ios" || target == "armv7-apple-ios" {
-        "iphoneos"
-    } else {
-        return None;
-    };
-
-    Some(
-        bossy::Command::impure("xcrun")
-            .with_args(&["--sdk", sdk, "--show-sdk-path"])
-            .run_and_wait_for_str(|s| s.trim().to_string())
-            .expect("xcrun command failed"),
-    )
+pub fn target_triple() -> TargetTriple {
+    try_target_triple().unwrap_or_else(|err| panic!("{}", err))
 }
 
 pub fn default_clang_args(
@@ -76,68 +40,66 @@ pub fn default_clang_args(
     apple_args: &[String],
     android_args: &[String],
 ) -> Vec<String> {
-    let target = target();
+    try_default_clang_args(includes, apple_args, android_args)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
 
-    let mut args = vec!["-xc++".into(), "-stdlib=libc++".into(), CPP_VERSION.into()];
+pub fn try_default_clang_args(
+    includes: &[&str],
+    apple_args: &[String],
+    android_args: &[String],
+) -> Result<Vec<String>> {
+    try_default_clang_args_with_std(CppStandard::default(), includes, apple_args, android_args)
+}
 
-    if target.contains("apple") {
-        if let Some(sdk_path) = sdk_path(&target) {
-            args.push("-isysroot".into());
-            args.push(sdk_path);
-        }
-        apple_args.iter().for_each(|arg| args.push(arg.to_string()));
-    }
+pub fn default_clang_args_with_std(
+    std: CppStandard,
+    includes: &[&str],
+    apple_args: &[String],
+    android_args: &[String],
+) -> Vec<String> {
+    try_default_clang_args_with_std(std, includes, apple_args, android_args)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
 
-    if target.contains("android") {
-        android_args
-            .iter()
-            .for_each(|arg| args.push(arg.to_string()));
-    }
+pub fn try_default_clang_args_with_std(
+    std: CppStandard,
+    includes: &[&str],
+    apple_args: &[String],
+    android_args: &[String],
+) -> Result<Vec<String>> {
+    ClangArgs::new()
+        .std(std)
+        .includes(includes.iter().copied())
+        .for_os(TargetOs::is_apple, apple_args.iter().cloned())
+        .for_os(TargetOs::is_android, android_args.iter().cloned())
+        .try_build()
+}
 
-    // https://github.com/rust-lang/rust-bindgen/issues/1211
-    let target = if target == "aarch64-apple-ios" {
-        String::from("arm64-apple-ios")
-    } else {
-        target
-    };
+pub fn recursive_link_dir(link_dir: impl AsRef<Path>, filters: &[&str]) {
+    try_recursive_link_dir(link_dir, filters).unwrap_or_else(|err| panic!("{}", err))
+}
 
-    includes
-        .iter()
-        .for_each(|include| args.push(format!("-I{}", include)));
+/// Links every framework under `link_dir`, including the target's slice of
+/// each xcframework, skipping paths with a component equal to one of
+/// `filters`. Loose libraries are left alone; use [`LinkScanner`] to link
+/// those. See [`LinkScanner::frameworks_only`].
+pub fn try_recursive_link_dir(link_dir: impl AsRef<Path>, filters: &[&str]) -> Result<()> {
+    try_emit_link_items(&try_discover_link_dir(link_dir, filters)?)
+}
 
-    args.push(format!("--target={}", target));
-    args
+/// Like [`try_recursive_link_dir`], but returns what it would link instead of
+/// emitting it.
+pub fn try_discover_link_dir(
+    link_dir: impl AsRef<Path>,
+    filters: &[&str],
+) -> Result<Vec<LinkItem>> {
+    LinkScanner::new(link_dir.as_ref())
+        .filters(filters.iter().copied())
+        .frameworks_only(true)
+        .try_discover()
 }
 
-pub fn recursive_link_dir(link_dir: impl AsRef<Path>, filters: &[&'static str]) {
-    let frameworks = walkdir::WalkDir::new(link_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            let components = e.path().components();
-            for component in components {
-                for filter in filters {
-                    if *filter == component.as_os_str().to_str().unwrap() {
-                        return false;
-                    }
-                }
-            }
-            true
-        })
-        .filter(|dir| {
-            dir.path()
-                .extension()
-                .map_or_else(|| false, |extension| extension == "framework")
-        })
-        .collect::<Vec<_>>();
-    for framework in frameworks {
-        let path = framework.path();
-        let parent = path.parent().unwrap();
-        println!("cargo:rustc-link-search=framework={}", parent.display());
-        let framework = path.file_stem().unwrap();
-        println!(
-            "cargo:rustc-link-lib=framework={}",
-            framework.to_str().unwrap()
-        );
-    }
+pub fn discover_link_dir(link_dir: impl AsRef<Path>, filters: &[&str]) -> Vec<LinkItem> {
+    try_discover_link_dir(link_dir, filters).unwrap_or_else(|err| panic!("{}", err))
 }
@@ -0,0 +1,892 @@
+use crate::{
+    try_emit_rerun_if_changed_with, BuildEnv, CargoDirectives, Error, RerunGranularity, Result,
+    TargetOs, XcFramework,
+};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    Static,
+    Dylib,
+    Framework,
+}
+
+impl LinkKind {
+    /// The kind as written in `cargo:rustc-link-lib=<kind>=<name>`.
+    pub fn lib_kind(&self) -> &'static str {
+        match self {
+            Self::Static => "static",
+            Self::Dylib => "dylib",
+            Self::Framework => "framework",
+        }
+    }
+
+    /// The kind as written in `cargo:rustc-link-search=<kind>=<dir>`.
+    pub fn search_kind(&self) -> &'static str {
+        match self {
+            Self::Static | Self::Dylib => "native",
+            Self::Framework => "framework",
+        }
+    }
+}
+
+/// What to do when a library is available both statically and dynamically.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkPolicy {
+    #[default]
+    PreferStatic,
+    PreferDynamic,
+    /// Ignore dynamic libraries entirely.
+    StaticOnly,
+    /// Ignore static libraries entirely.
+    DynamicOnly,
+}
+
+/// Something to link, as found by [`LinkScanner`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkItem {
+    pub kind: LinkKind,
+    pub name: String,
+    /// Whether `name` is the file name itself, rather than the part between
+    /// `lib` and the extension.
+    pub verbatim: bool,
+    /// The directory to add to the linker's search path.
+    pub search_path: PathBuf,
+    /// The framework or library itself.
+    pub source_path: PathBuf,
+    /// Whether to weak-link this, so that it's allowed to be missing at
+    /// runtime. Only frameworks and dynamic libraries can be weak-linked.
+    pub weak: bool,
+}
+
+impl LinkItem {
+    pub fn new(
+        kind: LinkKind,
+        name: impl Into<String>,
+        source_path: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let source_path = source_path.into();
+        Ok(Self {
+            kind,
+            name: name.into(),
+            verbatim: false,
+            search_path: parent(&source_path)?,
+            source_path,
+            weak: false,
+        })
+    }
+
+    /// The `cargo:rustc-link-lib` value, e.g. `framework=Foo`.
+    pub fn lib_arg(&self) -> String {
+        let modifiers = if self.verbatim { ":+verbatim" } else { "" };
+        format!("{}{}={}", self.kind.lib_kind(), modifiers, self.name)
+    }
+
+    /// The `cargo:rustc-link-arg` values that weak-link this, e.g.
+    /// `-weak_framework Foo`. These replace the `cargo:rustc-link-lib` line,
+    /// and are empty unless `weak` is set on a framework or dynamic library.
+    ///
+    /// Unlike `rustc-link-lib`, cargo only passes `rustc-link-arg` to the
+    /// package's own binaries, tests, examples, benches and cdylibs, never to
+    /// crates that depend on it. A library crate that weak-links something
+    /// leaves it unlinked for its dependents.
+    pub fn weak_link_args(&self) -> Result<Vec<String>> {
+        if !self.weak {
+            return Ok(Vec::new());
+        }
+        Ok(match self.kind {
+            LinkKind::Static => Vec::new(),
+            LinkKind::Framework => vec!["-weak_framework".into(), self.name.clone()],
+            LinkKind::Dylib if self.verbatim => {
+                let source_path = self
+                    .source_path
+                    .to_str()
+                    .ok_or_else(|| Error::InvalidPath(self.source_path.clone()))?;
+                vec!["-weak_library".into(), source_path.into()]
+            }
+            LinkKind::Dylib => vec![format!("-weak-l{}", self.name)],
+        })
+    }
+
+    /// The `cargo:rustc-link-search` value, e.g. `framework=/path/to/dir`.
+    pub fn search_arg(&self) -> Result<String> {
+        let search_path = self
+            .search_path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(self.search_path.clone()))?;
+        Ok(format!("{}={}", self.kind.search_kind(), search_path))
+    }
+
+    /// Whether this lives in an SDK or the OS's own framework directories, in
+    /// which case its headers should be treated like system headers.
+    pub fn is_system(&self) -> bool {
+        self.search_path.starts_with("/System/Library")
+            || self.search_path.starts_with("/Library/Frameworks")
+            || self.search_path.components().any(|component| {
+                Path::new(component.as_os_str()).extension() == Some(OsStr::new("sdk"))
+            })
+    }
+}
+
+/// Emits `cargo:rustc-link-search` (once per directory) and
+/// `cargo:rustc-link-lib` for each of `items`, or `cargo:rustc-link-arg` for
+/// weak ones, along with a `cargo:warning` that those don't reach dependent
+/// crates (see [`LinkItem::weak_link_args`]).
+pub fn try_emit_link_items(items: &[LinkItem]) -> Result<()> {
+    try_emit_link_items_with(items, &mut CargoDirectives::stdout())
+}
+
+/// Like [`try_emit_link_items`], but writing to `directives`.
+pub fn try_emit_link_items_with<W: Write>(
+    items: &[LinkItem],
+    directives: &mut CargoDirectives<W>,
+) -> Result<()> {
+    let mut search_args = HashSet::new();
+    for item in items {
+        let search_arg = item.search_arg()?;
+        if search_args.insert(search_arg.clone()) {
+            directives.link_search(&search_arg)?;
+        }
+    }
+    let mut weak_names = Vec::new();
+    for item in items {
+        let weak_link_args = item.weak_link_args()?;
+        if weak_link_args.is_empty() {
+            directives.link_lib(&item.lib_arg())?;
+        } else {
+            weak_names.push(format!("`{}`", item.name));
+        }
+        for arg in weak_link_args {
+            directives.link_arg(&arg)?;
+        }
+    }
+    if !weak_names.is_empty() {
+        directives.warning(&format!(
+            "weak-linking {} with `rustc-link-arg`, which only applies to this package's own \
+             binaries, tests, examples, benches and cdylibs, not to crates depending on it",
+            weak_names.join(", ")
+        ))?;
+    }
+    Ok(())
+}
+
+pub fn emit_link_items(items: &[LinkItem]) {
+    try_emit_link_items(items).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Finds everything linkable under a directory: static libraries (`.a`),
+/// dynamic libraries (`.dylib`, `.so`), frameworks and xcframeworks.
+#[derive(Debug, Clone)]
+pub struct LinkScanner {
+    root: PathBuf,
+    filters: Vec<String>,
+    globs: Vec<String>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    weak: Vec<String>,
+    rerun: Option<RerunGranularity>,
+    policy: LinkPolicy,
+    target: Option<TargetOs>,
+    frameworks_only: bool,
+}
+
+impl LinkScanner {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            filters: Vec::new(),
+            globs: Vec::new(),
+            max_depth: None,
+            follow_links: false,
+            weak: Vec::new(),
+            rerun: None,
+            policy: LinkPolicy::default(),
+            target: None,
+            frameworks_only: false,
+        }
+    }
+
+    /// Skips anything with a path component equal to `component`.
+    pub fn filter(mut self, component: impl Into<String>) -> Self {
+        self.filters.push(component.into());
+        self
+    }
+
+    pub fn filters(mut self, components: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.filters.extend(components.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds a glob matched against paths relative to the root, e.g.
+    /// `**/Debug/**`. Patterns starting with `!` exclude matching paths (and
+    /// everything under them); any other pattern is an include, and once
+    /// there's at least one include, only artifacts matching one are kept.
+    pub fn glob(mut self, pattern: impl Into<String>) -> Self {
+        self.globs.push(pattern.into());
+        self
+    }
+
+    pub fn globs(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.globs.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Stops descending past `depth` levels below the root, which is depth 0.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Whether to follow symlinked directories. Off by default.
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Weak-links the framework or dynamic library called `name` (e.g.
+    /// `Foo` for `Foo.framework` or `libFoo.dylib`), so the binary still
+    /// launches on OS versions that don't have it. Only applies to Apple
+    /// targets, since other linkers don't support weak linking this way.
+    ///
+    /// Weak linking needs `cargo:rustc-link-arg`, which cargo doesn't pass on
+    /// to dependent crates, so this is only useful in packages that build the
+    /// final binary or cdylib themselves. A `-sys` crate that weak-links a
+    /// framework leaves its dependents without it.
+    pub fn weak(mut self, name: impl Into<String>) -> Self {
+        self.weak.push(name.into());
+        self
+    }
+
+    pub fn weak_names(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.weak.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Makes [`LinkScanner::link`] also emit `cargo:rerun-if-changed`, either
+    /// for every directory walked or for every library and framework found.
+    /// Note that once a build script emits any of these, cargo stops
+    /// rerunning it whenever any file in the package changes.
+    pub fn rerun_if_changed(mut self, granularity: RerunGranularity) -> Self {
+        self.rerun = Some(granularity);
+        self
+    }
+
+    pub fn policy(mut self, policy: LinkPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Overrides the target used to pick xcframework slices, which otherwise
+    /// comes from `TARGET`.
+    pub fn target(mut self, target: TargetOs) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Only looks for frameworks: `.framework` bundles, and the slice of each
+    /// xcframework built for the target if that's a framework too. Loose
+    /// libraries are skipped, and so are xcframeworks that can't be linked
+    /// (e.g. with no slice for the target) rather than failing the scan.
+    pub fn frameworks_only(mut self, frameworks_only: bool) -> Self {
+        self.frameworks_only = frameworks_only;
+        self
+    }
+
+    pub fn discover(&self) -> Vec<LinkItem> {
+        self.try_discover().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Emits `cargo:rustc-link-search` and `cargo:rustc-link-lib` for
+    /// everything found.
+    pub fn link(&self) {
+        self.try_link().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_link(&self) -> Result<()> {
+        self.try_link_with(&BuildEnv::from_env())
+    }
+
+    /// Like [`LinkScanner::try_link`], but reading `TARGET` from `env` and
+    /// emitting through it.
+    pub fn try_link_with(&self, env: &BuildEnv) -> Result<()> {
+        let (found, walked_dirs, artifacts) = self.scan(env)?;
+        env.try_emit(|directives| {
+            match self.rerun {
+                Some(RerunGranularity::Directory) => {
+                    try_emit_rerun_if_changed_with(&walked_dirs, env, directives)?
+                }
+                Some(RerunGranularity::File) => {
+                    try_emit_rerun_if_changed_with(&artifacts, env, directives)?
+                }
+                None => (),
+            }
+            try_emit_link_items_with(&found, directives)
+        })
+    }
+
+    /// Finds everything to link, without emitting anything.
+    pub fn try_discover(&self) -> Result<Vec<LinkItem>> {
+        self.scan(&BuildEnv::from_env()).map(|(found, _, _)| found)
+    }
+
+    // Returns what to link, along with every directory walked and every
+    // artifact considered, for rerun tracking.
+    fn scan(&self, env: &BuildEnv) -> Result<(Vec<LinkItem>, Vec<PathBuf>, Vec<PathBuf>)> {
+        let (includes, excludes) = self.glob_sets()?;
+        let mut found = Vec::new();
+        let mut walked_dirs = Vec::new();
+        let mut artifacts = Vec::new();
+        let mut walker = walkdir::WalkDir::new(&self.root)
+            .follow_links(self.follow_links)
+            .sort_by_file_name();
+        if let Some(depth) = self.max_depth {
+            walker = walker.max_depth(depth);
+        }
+        let mut walker = walker.into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let path = entry.path();
+            let relative = path.strip_prefix(&self.root).unwrap_or(path);
+            // Directories are also matched with a trailing separator, so that
+            // `!**/Debug/**` prunes `Debug` itself rather than each entry in it.
+            let excluded = excludes.is_match(relative)
+                || (entry.file_type().is_dir() && excludes.is_match(relative.join("")));
+            if self.is_filtered(path) || excluded {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+            let included = includes.is_empty() || includes.is_match(relative);
+            match path.extension().and_then(OsStr::to_str) {
+                Some("framework") => {
+                    if included {
+                        found.push(framework(path)?);
+                        artifacts.push(path.to_owned());
+                    }
+                    walker.skip_current_dir();
+                }
+                Some("xcframework") => {
+                    if included {
+                        if let Some(os) = self.target_os(env)?.filter(TargetOs::is_apple) {
+                            match xcframework_slice(path, &os) {
+                                Ok(item)
+                                    if !self.frameworks_only
+                                        || item.kind == LinkKind::Framework =>
+                                {
+                                    found.push(item);
+                                    artifacts.push(path.to_owned());
+                                }
+                                Ok(_) => (),
+                                Err(Error::InvalidXcFramework { .. }) if self.frameworks_only => (),
+                                Err(err) => return Err(err),
+                            }
+                        }
+                    }
+                    walker.skip_current_dir();
+                }
+                // `is_file` follows symlinks, since versioned libraries are
+                // usually reached through one (`libfoo.so -> libfoo.so.1`).
+                Some("a") if included && !self.frameworks_only && path.is_file() => {
+                    found.push(library_item(path, LinkKind::Static)?);
+                    artifacts.push(path.to_owned());
+                }
+                Some("dylib" | "so") if included && !self.frameworks_only && path.is_file() => {
+                    found.push(library_item(path, LinkKind::Dylib)?);
+                    artifacts.push(path.to_owned());
+                }
+                _ if entry.file_type().is_dir() => walked_dirs.push(path.to_owned()),
+                _ => (),
+            }
+        }
+        let mut found = self.apply_policy(dedupe_symlinked(found));
+        if !self.weak.is_empty() && self.target_os(env)?.is_some_and(|os| os.is_apple()) {
+            for item in &mut found {
+                item.weak = self.weak.contains(&item.name);
+            }
+        }
+        Ok((found, walked_dirs, artifacts))
+    }
+
+    fn target_os(&self, env: &BuildEnv) -> Result<Option<TargetOs>> {
+        match &self.target {
+            Some(target) => Ok(Some(target.clone())),
+            None => Ok(TargetOs::from_triple(env.try_target()?)),
+        }
+    }
+
+    fn is_filtered(&self, path: &Path) -> bool {
+        path.components().any(|component| {
+            self.filters
+                .iter()
+                .any(|filter| component.as_os_str() == filter.as_str())
+        })
+    }
+
+    // Returns the include and exclude sets, in that order.
+    fn glob_sets(&self) -> Result<(GlobSet, GlobSet)> {
+        let mut includes = GlobSetBuilder::new();
+        let mut excludes = GlobSetBuilder::new();
+        for pattern in &self.globs {
+            let (set, glob) = match pattern.strip_prefix('!') {
+                Some(glob) => (&mut excludes, glob),
+                None => (&mut includes, pattern.as_str()),
+            };
+            set.add(
+                GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| invalid_pattern(pattern, err))?,
+            );
+        }
+        let build = |set: GlobSetBuilder| set.build().map_err(|err| invalid_pattern("", err));
+        Ok((build(includes)?, build(excludes)?))
+    }
+
+    fn apply_policy(&self, items: Vec<LinkItem>) -> Vec<LinkItem> {
+        let has = |kind, name: &str| {
+            items
+                .iter()
+                .any(|item| item.kind == kind && item.name == name)
+        };
+        items
+            .iter()
+            .filter(|item| match (self.policy, item.kind) {
+                (_, LinkKind::Framework) => true,
+                (LinkPolicy::StaticOnly, kind) => kind == LinkKind::Static,
+                (LinkPolicy::DynamicOnly, kind) => kind == LinkKind::Dylib,
+                (LinkPolicy::PreferStatic, LinkKind::Dylib) => !has(LinkKind::Static, &item.name),
+                (LinkPolicy::PreferDynamic, LinkKind::Static) => !has(LinkKind::Dylib, &item.name),
+                (LinkPolicy::PreferStatic | LinkPolicy::PreferDynamic, _) => true,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn invalid_pattern(pattern: &str, err: globset::Error) -> Error {
+    Error::InvalidPattern {
+        pattern: err.glob().unwrap_or(pattern).into(),
+        reason: err.kind().to_string(),
+    }
+}
+
+fn parent(path: &Path) -> Result<PathBuf> {
+    path.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))
+}
+
+fn framework(path: &Path) -> Result<LinkItem> {
+    let name = path
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+    LinkItem::new(LinkKind::Framework, name, path)
+}
+
+// A library reachable under several names (`libfoo.dylib -> libfoo.1.dylib`)
+// is linked once, by its shortest name, which is the unversioned symlink.
+fn dedupe_symlinked(items: Vec<LinkItem>) -> Vec<LinkItem> {
+    let mut kept = Vec::new();
+    let mut by_file = HashMap::<_, usize>::new();
+    for item in items {
+        if item.kind == LinkKind::Framework {
+            kept.push(item);
+            continue;
+        }
+        let file = fs::canonicalize(&item.source_path).unwrap_or(item.source_path.clone());
+        match by_file.get(&(item.kind, file.clone())) {
+            Some(&index) if item.name.len() < kept[index].name.len() => kept[index] = item,
+            Some(_) => (),
+            None => {
+                by_file.insert((item.kind, file), kept.len());
+                kept.push(item);
+            }
+        }
+    }
+    kept
+}
+
+// `libfoo.a` links as `foo`. Anything without the `lib` prefix has to be
+// linked by its full file name instead.
+fn library_item(path: &Path, kind: LinkKind) -> Result<LinkItem> {
+    let file_name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+    let (name, verbatim) = match file_name.strip_prefix("lib") {
+        Some(rest) => (rest.rsplit_once('.').map_or(rest, |(name, _)| name), false),
+        None => (file_name, true),
+    };
+    Ok(LinkItem {
+        verbatim,
+        ..LinkItem::new(kind, name, path)?
+    })
+}
+
+// Picks the slice matching `os` out of the xcframework's `Info.plist`.
+fn xcframework_slice(path: &Path, os: &TargetOs) -> Result<LinkItem> {
+    let xcframework = XcFramework::from_path(path)?;
+    let no_slice = |reason: String| Error::InvalidXcFramework {
+        path: path.to_owned(),
+        reason,
+    };
+    let library = xcframework
+        .library_for(os)
+        .ok_or_else(|| no_slice(format!("no slice for `{}`", os.triple())))?;
+    let kind = library.kind().ok_or_else(|| {
+        no_slice(format!(
+            "don't know how to link {:?}",
+            library.path.display()
+        ))
+    })?;
+    let slice_path = xcframework.slice_dir(library).join(&library.path);
+    match kind {
+        LinkKind::Framework => framework(&slice_path),
+        kind => library_item(&slice_path, kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DirectiveSyntax, TargetTriple};
+
+    fn touch(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[cfg(unix)]
+    fn symlink(root: &Path, link: &str, target: &str) {
+        std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+    }
+
+    fn linux() -> TargetOs {
+        TargetOs::from_triple(TargetTriple::parse("x86_64-unknown-linux-gnu").unwrap()).unwrap()
+    }
+
+    fn scan(scanner: LinkScanner) -> Vec<(LinkKind, String, bool)> {
+        scanner
+            .target(linux())
+            .try_discover()
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.kind, item.name, item.verbatim))
+            .collect()
+    }
+
+    #[test]
+    fn names_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "libfoo.a");
+        touch(dir.path(), "libbar.dylib");
+        touch(dir.path(), "libbaz.so");
+        touch(dir.path(), "qux.a");
+        touch(dir.path(), "Foo.framework/Foo");
+        touch(dir.path(), "libfoo.h");
+        assert_eq!(
+            scan(LinkScanner::new(dir.path())),
+            [
+                (LinkKind::Framework, "Foo".into(), false),
+                (LinkKind::Dylib, "bar".into(), false),
+                (LinkKind::Dylib, "baz".into(), false),
+                (LinkKind::Static, "foo".into(), false),
+                (LinkKind::Static, "qux.a".into(), true),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_versioned_libraries_by_their_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "libfoo.so.1.2");
+        symlink(dir.path(), "libfoo.so.1", "libfoo.so.1.2");
+        symlink(dir.path(), "libfoo.so", "libfoo.so.1");
+        touch(dir.path(), "libbar.1.dylib");
+        symlink(dir.path(), "libbar.dylib", "libbar.1.dylib");
+        let items = LinkScanner::new(dir.path())
+            .target(linux())
+            .try_discover()
+            .unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (item.kind, item.name.as_str(), item.verbatim))
+                .collect::<Vec<_>>(),
+            [
+                (LinkKind::Dylib, "bar", false),
+                (LinkKind::Dylib, "foo", false),
+            ]
+        );
+        assert_eq!(items[0].source_path, dir.path().join("libbar.dylib"));
+        assert_eq!(items[1].source_path, dir.path().join("libfoo.so"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_dangling_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        symlink(dir.path(), "libfoo.so", "libfoo.so.1");
+        assert_eq!(scan(LinkScanner::new(dir.path())), []);
+    }
+
+    #[test]
+    fn applies_link_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "libboth.a");
+        touch(dir.path(), "libboth.so");
+        touch(dir.path(), "libstatic.a");
+        touch(dir.path(), "libdynamic.so");
+        let scan_with = |policy| scan(LinkScanner::new(dir.path()).policy(policy));
+        let both = |kind| (kind, "both".to_string(), false);
+        let only_static = (LinkKind::Static, "static".to_string(), false);
+        let only_dynamic = (LinkKind::Dylib, "dynamic".to_string(), false);
+        assert_eq!(
+            scan_with(LinkPolicy::PreferStatic),
+            [
+                both(LinkKind::Static),
+                only_dynamic.clone(),
+                only_static.clone()
+            ]
+        );
+        assert_eq!(
+            scan_with(LinkPolicy::PreferDynamic),
+            [
+                both(LinkKind::Dylib),
+                only_dynamic.clone(),
+                only_static.clone()
+            ]
+        );
+        assert_eq!(
+            scan_with(LinkPolicy::StaticOnly),
+            [both(LinkKind::Static), only_static]
+        );
+        assert_eq!(
+            scan_with(LinkPolicy::DynamicOnly),
+            [both(LinkKind::Dylib), only_dynamic]
+        );
+    }
+
+    #[test]
+    fn filters_and_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "Debug/libfoo.a");
+        touch(dir.path(), "Release/libfoo.a");
+        touch(dir.path(), "Release/extra/libbar.a");
+        touch(dir.path(), "Release/Foo.framework/Foo");
+        let names = |scanner: LinkScanner| {
+            scanner
+                .target(linux())
+                .try_discover()
+                .unwrap()
+                .into_iter()
+                .map(|item| {
+                    item.source_path
+                        .strip_prefix(dir.path())
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+        let release = [
+            PathBuf::from("Release/Foo.framework"),
+            "Release/extra/libbar.a".into(),
+            "Release/libfoo.a".into(),
+        ];
+        assert_eq!(names(LinkScanner::new(dir.path()).filter("Debug")), release);
+        assert_eq!(
+            names(LinkScanner::new(dir.path()).glob("!**/Debug/**")),
+            release
+        );
+        assert_eq!(
+            names(LinkScanner::new(dir.path()).glob("**/*.a").filter("extra")),
+            [PathBuf::from("Debug/libfoo.a"), "Release/libfoo.a".into()]
+        );
+        assert_eq!(
+            names(LinkScanner::new(dir.path()).max_depth(2).filter("Debug")),
+            [
+                PathBuf::from("Release/Foo.framework"),
+                "Release/libfoo.a".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn frameworks_only_links_frameworks_and_framework_slices() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "Foo.framework/Foo");
+        touch(dir.path(), "Debug/Bar.framework/Bar");
+        touch(dir.path(), "libfoo.a");
+        touch(dir.path(), "libfoo.dylib");
+        touch(dir.path(), "libfoo.so");
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/Foo.xcframework");
+        let vendored = dir.path().join("Vendored.xcframework");
+        fs::create_dir(&vendored).unwrap();
+        fs::copy(fixture.join("Info.plist"), vendored.join("Info.plist")).unwrap();
+        // Not a valid xcframework, which is skipped rather than failing.
+        touch(dir.path(), "Broken.xcframework/Info.plist");
+        let discover = |triple| {
+            LinkScanner::new(dir.path())
+                .filter("Debug")
+                .frameworks_only(true)
+                .target(TargetOs::from_triple(TargetTriple::parse(triple).unwrap()).unwrap())
+                .try_discover()
+                .unwrap()
+        };
+        let foo =
+            LinkItem::new(LinkKind::Framework, "Foo", dir.path().join("Foo.framework")).unwrap();
+        assert_eq!(
+            discover("aarch64-apple-ios-sim"),
+            [
+                foo.clone(),
+                LinkItem::new(
+                    LinkKind::Framework,
+                    "Foo",
+                    vendored.join("ios-arm64_x86_64-simulator/Foo.framework")
+                )
+                .unwrap(),
+            ]
+        );
+        // The macOS slice is a static library, and there's no tvOS slice.
+        for triple in ["aarch64-apple-darwin", "aarch64-apple-tvos"] {
+            assert_eq!(discover(triple), std::slice::from_ref(&foo), "{}", triple);
+        }
+    }
+
+    #[test]
+    fn weak_links_only_on_apple_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "Foo.framework/Foo");
+        touch(dir.path(), "libbar.dylib");
+        let weak = |triple| {
+            LinkScanner::new(dir.path())
+                .weak_names(["Foo", "bar"])
+                .target(TargetOs::from_triple(TargetTriple::parse(triple).unwrap()).unwrap())
+                .try_discover()
+                .unwrap()
+                .into_iter()
+                .map(|item| item.weak)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(weak("aarch64-apple-darwin"), [true, true]);
+        assert_eq!(weak("x86_64-unknown-linux-gnu"), [false, false]);
+    }
+
+    #[test]
+    fn links_through_build_env() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "libfoo.a");
+        let env = BuildEnv::from_map([("TARGET", "x86_64-unknown-linux-gnu")])
+            .syntax(DirectiveSyntax::DoubleColon);
+        LinkScanner::new(dir.path()).try_link_with(&env).unwrap();
+        assert_eq!(
+            env.emitted(),
+            [
+                format!("cargo::rustc-link-search=native={}", dir.path().display()),
+                "cargo::rustc-link-lib=static=foo".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reruns_for_walked_dirs_or_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "lib/libfoo.a");
+        touch(dir.path(), "lib/Foo.framework/Headers/Foo.h");
+        touch(dir.path(), "Debug/libfoo.a");
+        let rerun_lines = |granularity| {
+            let env = BuildEnv::from_map([("TARGET", "x86_64-unknown-linux-gnu")]);
+            LinkScanner::new(dir.path())
+                .filter("Debug")
+                .rerun_if_changed(granularity)
+                .try_link_with(&env)
+                .unwrap();
+            env.emitted()
+                .into_iter()
+                .filter_map(|line| {
+                    line.strip_prefix("cargo:rerun-if-changed=")
+                        .map(PathBuf::from)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rerun_lines(RerunGranularity::Directory),
+            [dir.path().to_owned(), dir.path().join("lib")]
+        );
+        assert_eq!(
+            rerun_lines(RerunGranularity::File),
+            [
+                dir.path().join("lib/Foo.framework"),
+                dir.path().join("lib/libfoo.a")
+            ]
+        );
+    }
+
+    #[test]
+    fn emits_link_items() {
+        let mut items = vec![
+            LinkItem::new(LinkKind::Framework, "Foo", "/f/Foo.framework").unwrap(),
+            LinkItem::new(LinkKind::Static, "foo", "/l/libfoo.a").unwrap(),
+            LinkItem {
+                verbatim: true,
+                ..LinkItem::new(LinkKind::Static, "bar.a", "/l/bar.a").unwrap()
+            },
+            LinkItem::new(LinkKind::Dylib, "baz", "/d/libbaz.dylib").unwrap(),
+            LinkItem {
+                verbatim: true,
+                ..LinkItem::new(LinkKind::Dylib, "qux.dylib", "/d/qux.dylib").unwrap()
+            },
+        ];
+        let emit = |items: &[LinkItem]| {
+            let mut directives =
+                CargoDirectives::new(Vec::new()).syntax(DirectiveSyntax::DoubleColon);
+            try_emit_link_items_with(items, &mut directives).unwrap();
+            String::from_utf8(directives.into_inner()).unwrap()
+        };
+        assert_eq!(
+            emit(&items),
+            "cargo::rustc-link-search=framework=/f\n\
+             cargo::rustc-link-search=native=/l\n\
+             cargo::rustc-link-search=native=/d\n\
+             cargo::rustc-link-lib=framework=Foo\n\
+             cargo::rustc-link-lib=static=foo\n\
+             cargo::rustc-link-lib=static:+verbatim=bar.a\n\
+             cargo::rustc-link-lib=dylib=baz\n\
+             cargo::rustc-link-lib=dylib:+verbatim=qux.dylib\n"
+        );
+
+        for item in &mut items {
+            item.weak = true;
+        }
+        assert_eq!(
+            emit(&items),
+            "cargo::rustc-link-search=framework=/f\n\
+             cargo::rustc-link-search=native=/l\n\
+             cargo::rustc-link-search=native=/d\n\
+             cargo::rustc-link-arg=-weak_framework\n\
+             cargo::rustc-link-arg=Foo\n\
+             cargo::rustc-link-lib=static=foo\n\
+             cargo::rustc-link-lib=static:+verbatim=bar.a\n\
+             cargo::rustc-link-arg=-weak-lbaz\n\
+             cargo::rustc-link-arg=-weak_library\n\
+             cargo::rustc-link-arg=/d/qux.dylib\n\
+             cargo::warning=weak-linking `Foo`, `baz`, `qux.dylib` with `rustc-link-arg`, which \
+             only applies to this package's own binaries, tests, examples, benches and cdylibs, \
+             not to crates depending on it\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{BuildEnv, CargoDirectives, Result};
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// How finely to track paths with `cargo:rerun-if-changed`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RerunGranularity {
+    /// One directive per directory. Cargo checks everything under a
+    /// directory, so this also catches files being added.
+    #[default]
+    Directory,
+    /// One directive per file, so that changes to unrelated files sharing a
+    /// directory don't trigger a rerun.
+    File,
+}
+
+/// Emits `cargo:rerun-if-changed` once for each of `paths`, skipping anything
+/// inside `OUT_DIR`, since that's written by the build script itself.
+pub fn try_emit_rerun_if_changed(paths: &[PathBuf]) -> Result<()> {
+    try_emit_rerun_if_changed_with(paths, &BuildEnv::from_env(), &mut CargoDirectives::stdout())
+}
+
+/// Like [`try_emit_rerun_if_changed`], but reading `OUT_DIR` from `env` and
+/// writing to `directives`.
+pub fn try_emit_rerun_if_changed_with<W: Write>(
+    paths: &[PathBuf],
+    env: &BuildEnv,
+    directives: &mut CargoDirectives<W>,
+) -> Result<()> {
+    let out_dir = env.cargo_var("OUT_DIR")?.map(PathBuf::from);
+    let mut emitted = HashSet::new();
+    for path in paths {
+        if out_dir
+            .as_ref()
+            .is_some_and(|out_dir| path.starts_with(out_dir))
+        {
+            continue;
+        }
+        if emitted.insert(path) {
+            directives.rerun_if_changed(path)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn emit_rerun_if_changed(paths: &[PathBuf]) {
+    try_emit_rerun_if_changed(paths).unwrap_or_else(|err| panic!("{}", err))
+}
+
+// The paths to track for `dir` at the given granularity.
+pub(crate) fn rerun_paths(dir: &Path, granularity: RerunGranularity) -> Vec<PathBuf> {
+    match granularity {
+        RerunGranularity::Directory => vec![dir.to_owned()],
+        RerunGranularity::File => walkdir::WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(walkdir::DirEntry::into_path)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DirectiveSyntax;
+    use std::fs;
+
+    fn emitted(paths: &[&str], env: &BuildEnv) -> String {
+        let paths = paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let mut directives = CargoDirectives::new(Vec::new()).syntax(DirectiveSyntax::DoubleColon);
+        try_emit_rerun_if_changed_with(&paths, env, &mut directives).unwrap();
+        String::from_utf8(directives.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn skips_out_dir_and_duplicates() {
+        let env = BuildEnv::from_map([("OUT_DIR", "/target/build/foo/out")]);
+        assert_eq!(
+            emitted(
+                &[
+                    "/src/include",
+                    "/target/build/foo/out/bindings.rs",
+                    "/target/build/foo/out",
+                    "/src/include",
+                    "/target/build/foo/output",
+                    "/src/lib",
+                ],
+                &env
+            ),
+            "cargo::rerun-if-changed=/src/include\n\
+             cargo::rerun-if-changed=/target/build/foo/output\n\
+             cargo::rerun-if-changed=/src/lib\n"
+        );
+        // `OUT_DIR` is set by cargo, so it's not worth a rerun directive.
+        assert_eq!(env.read_vars(), ["OUT_DIR"]);
+        assert!(env.rerun_vars().is_empty());
+    }
+
+    #[test]
+    fn keeps_everything_without_out_dir() {
+        let env = BuildEnv::from_map(Vec::<(String, String)>::new());
+        assert_eq!(
+            emitted(&["/a", "/b"], &env),
+            "cargo::rerun-if-changed=/a\ncargo::rerun-if-changed=/b\n"
+        );
+    }
+
+    #[test]
+    fn tracks_dirs_or_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["b.h", "a.h", "nested/c.h"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        assert_eq!(
+            rerun_paths(dir.path(), RerunGranularity::Directory),
+            [dir.path()]
+        );
+        assert_eq!(
+            rerun_paths(dir.path(), RerunGranularity::File),
+            [
+                dir.path().join("a.h"),
+                dir.path().join("b.h"),
+                dir.path().join("nested/c.h"),
+            ]
+        );
+        assert!(rerun_paths(&dir.path().join("missing"), RerunGranularity::File).is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{Error, Result};
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+/// Runs external tools like `xcrun`. Everything in this crate that shells out
+/// goes through one of these, so that it can be swapped for a
+/// [`ScriptedRunner`] where the tools aren't available.
+pub trait ToolRunner: fmt::Debug {
+    /// Runs `program` with `args`, returning its stdout if it succeeds.
+    fn run(&self, program: &str, args: &[&str]) -> Result<String>;
+}
+
+/// Runs tools as real subprocesses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl ToolRunner for ProcessRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<String> {
+        Ok(bossy::Command::impure(program)
+            .with_args(args)
+            .run_and_wait_for_string()?)
+    }
+}
+
+/// Returns canned output instead of running anything, keyed by the full
+/// command line (e.g. `xcrun --sdk iphoneos --show-sdk-path`).
+#[derive(Debug, Default)]
+pub struct ScriptedRunner {
+    responses: HashMap<String, std::result::Result<String, String>>,
+    calls: Mutex<Vec<String>>,
+}
+
+impl ScriptedRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `command` succeed, printing `stdout`.
+    pub fn stdout(mut self, command: impl Into<String>, stdout: impl Into<String>) -> Self {
+        self.responses.insert(command.into(), Ok(stdout.into()));
+        self
+    }
+
+    /// Makes `command` fail, printing `stderr`.
+    pub fn stderr(mut self, command: impl Into<String>, stderr: impl Into<String>) -> Self {
+        self.responses.insert(command.into(), Err(stderr.into()));
+        self
+    }
+
+    /// Every command run so far, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl ToolRunner for ScriptedRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<String> {
+        let command = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.calls.lock().unwrap().push(command.clone());
+        match self.responses.get(&command) {
+            Some(Ok(stdout)) => Ok(stdout.clone()),
+            Some(Err(stderr)) => Err(Error::ToolFailed {
+                command,
+                code: Some(1),
+                stderr: stderr.clone(),
+            }),
+            None => Err(Error::ToolFailed {
+                command,
+                code: None,
+                stderr: "no scripted response for this command".into(),
+            }),
+        }
+    }
+}
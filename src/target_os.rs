@@ -0,0 +1,270 @@
+use crate::{BuildEnv, Env, Os, Result, TargetTriple};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetOs {
+    Ios(TargetTriple),
+    /// Along with the API level, if known.
+    Android(TargetTriple, Option<u32>),
+    MacOs(TargetTriple),
+    /// iOS apps running on macOS (`*-apple-ios-macabi`).
+    MacCatalyst(TargetTriple),
+    TvOs(TargetTriple),
+    WatchOs(TargetTriple),
+    VisionOs(TargetTriple),
+    Linux(TargetTriple),
+    /// Both the `msvc` and `gnu` environments.
+    Windows(TargetTriple),
+    /// `wasm32-unknown-emscripten`, `wasm32-wasip1`, `wasm32-unknown-unknown`, ...
+    Wasm(TargetTriple),
+}
+
+impl TargetOs {
+    pub fn detect() -> Option<Self> {
+        Self::try_detect().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Detects the target from `TARGET`, picking up the Android API level from
+    /// the environment (see [`ANDROID_API_LEVEL_VARS`](crate::ANDROID_API_LEVEL_VARS)).
+    pub fn try_detect() -> Result<Option<Self>> {
+        Self::try_detect_from(&BuildEnv::from_env())
+    }
+
+    /// Like [`TargetOs::try_detect`], but reading from `env`.
+    pub fn try_detect_from(env: &BuildEnv) -> Result<Option<Self>> {
+        Self::from_triple(env.try_target()?)
+            .map(|os| os.try_with_android_api_level_from(env))
+            .transpose()
+    }
+
+    /// Fills in the Android API level from the environment, if it isn't
+    /// already set.
+    pub fn try_with_env_android_api_level(self) -> Result<Self> {
+        self.try_with_android_api_level_from(&BuildEnv::from_env())
+    }
+
+    /// Like [`TargetOs::try_with_env_android_api_level`], but reading from
+    /// `env`.
+    pub fn try_with_android_api_level_from(self, env: &BuildEnv) -> Result<Self> {
+        Ok(match self {
+            TargetOs::Android(triple, None) => {
+                TargetOs::Android(triple, env.try_android_api_level()?)
+            }
+            os => os,
+        })
+    }
+
+    pub fn with_android_api_level(self, api_level: u32) -> Self {
+        match self {
+            TargetOs::Android(triple, _) => TargetOs::Android(triple, Some(api_level)),
+            os => os,
+        }
+    }
+
+    pub fn android_api_level(&self) -> Option<u32> {
+        match self {
+            TargetOs::Android(_, api_level) => *api_level,
+            _ => None,
+        }
+    }
+
+    pub fn from_triple(triple: TargetTriple) -> Option<Self> {
+        if triple.arch.is_wasm() {
+            return Some(Self::Wasm(triple));
+        }
+        match triple.os {
+            Os::Ios if triple.is_mac_catalyst() => Some(Self::MacCatalyst(triple)),
+            Os::Ios => Some(Self::Ios(triple)),
+            Os::Darwin => Some(Self::MacOs(triple)),
+            Os::TvOs => Some(Self::TvOs(triple)),
+            Os::WatchOs => Some(Self::WatchOs(triple)),
+            Os::VisionOs => Some(Self::VisionOs(triple)),
+            Os::Windows => Some(Self::Windows(triple)),
+            Os::Linux if triple.is_android() => Some(Self::Android(triple, None)),
+            Os::Linux => Some(Self::Linux(triple)),
+            _ => None,
+        }
+    }
+
+    pub fn triple(&self) -> &TargetTriple {
+        match self {
+            TargetOs::Ios(triple)
+            | TargetOs::Android(triple, _)
+            | TargetOs::MacOs(triple)
+            | TargetOs::MacCatalyst(triple)
+            | TargetOs::TvOs(triple)
+            | TargetOs::WatchOs(triple)
+            | TargetOs::VisionOs(triple)
+            | TargetOs::Linux(triple)
+            | TargetOs::Windows(triple)
+            | TargetOs::Wasm(triple) => triple,
+        }
+    }
+
+    pub fn is_ios(&self) -> bool {
+        matches!(self, TargetOs::Ios(_))
+    }
+    pub fn is_android(&self) -> bool {
+        matches!(self, TargetOs::Android(..))
+    }
+    pub fn is_macos(&self) -> bool {
+        matches!(self, TargetOs::MacOs(_))
+    }
+    pub fn is_mac_catalyst(&self) -> bool {
+        matches!(self, TargetOs::MacCatalyst(_))
+    }
+    pub fn is_tvos(&self) -> bool {
+        matches!(self, TargetOs::TvOs(_))
+    }
+    pub fn is_watchos(&self) -> bool {
+        matches!(self, TargetOs::WatchOs(_))
+    }
+    pub fn is_visionos(&self) -> bool {
+        matches!(self, TargetOs::VisionOs(_))
+    }
+    pub fn is_linux(&self) -> bool {
+        matches!(self, TargetOs::Linux(_))
+    }
+    pub fn is_windows(&self) -> bool {
+        matches!(self, TargetOs::Windows(_))
+    }
+    pub fn is_windows_msvc(&self) -> bool {
+        matches!(self, TargetOs::Windows(triple) if triple.env == Some(Env::Msvc))
+    }
+    pub fn is_windows_gnu(&self) -> bool {
+        matches!(
+            self,
+            TargetOs::Windows(triple) if matches!(triple.env, Some(Env::Gnu(_) | Env::GnuLlvm))
+        )
+    }
+    pub fn is_wasm(&self) -> bool {
+        matches!(self, TargetOs::Wasm(_))
+    }
+    pub fn is_emscripten(&self) -> bool {
+        matches!(self, TargetOs::Wasm(triple) if triple.os == Os::Emscripten)
+    }
+    pub fn is_wasi(&self) -> bool {
+        matches!(self, TargetOs::Wasm(triple) if matches!(triple.os, Os::Wasi(_)))
+    }
+
+    /// Whether this is an Apple simulator, either explicitly (`-sim`) or by
+    /// being an Intel iOS, tvOS or watchOS target.
+    pub fn is_simulator(&self) -> bool {
+        self.is_apple() && crate::apple::is_simulator_triple(self.triple())
+    }
+
+    /// Any of the Apple platforms.
+    pub fn is_apple(&self) -> bool {
+        matches!(
+            self,
+            TargetOs::Ios(_)
+                | TargetOs::MacOs(_)
+                | TargetOs::MacCatalyst(_)
+                | TargetOs::TvOs(_)
+                | TargetOs::WatchOs(_)
+                | TargetOs::VisionOs(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os(triple: &str) -> Option<TargetOs> {
+        TargetOs::from_triple(TargetTriple::parse(triple).unwrap())
+    }
+
+    #[test]
+    fn classifies_targets() {
+        type Variant = fn(TargetTriple) -> TargetOs;
+        let android: Variant = |triple| TargetOs::Android(triple, None);
+        for (triple, variant) in [
+            ("aarch64-apple-ios", Some(TargetOs::Ios as Variant)),
+            ("aarch64-apple-ios-sim", Some(TargetOs::Ios)),
+            ("aarch64-apple-ios-macabi", Some(TargetOs::MacCatalyst)),
+            ("x86_64-apple-ios-macabi", Some(TargetOs::MacCatalyst)),
+            ("aarch64-apple-darwin", Some(TargetOs::MacOs)),
+            ("aarch64-apple-tvos", Some(TargetOs::TvOs)),
+            ("aarch64-apple-watchos", Some(TargetOs::WatchOs)),
+            ("aarch64-apple-visionos", Some(TargetOs::VisionOs)),
+            ("aarch64-linux-android", Some(android)),
+            ("armv7-linux-androideabi", Some(android)),
+            ("x86_64-unknown-linux-gnu", Some(TargetOs::Linux)),
+            ("aarch64-unknown-linux-musl", Some(TargetOs::Linux)),
+            ("x86_64-pc-windows-msvc", Some(TargetOs::Windows)),
+            ("x86_64-pc-windows-gnu", Some(TargetOs::Windows)),
+            ("aarch64-pc-windows-gnullvm", Some(TargetOs::Windows)),
+            ("wasm32-unknown-emscripten", Some(TargetOs::Wasm)),
+            ("wasm32-wasip1", Some(TargetOs::Wasm)),
+            ("wasm32-wasip1-threads", Some(TargetOs::Wasm)),
+            ("wasm32-unknown-unknown", Some(TargetOs::Wasm)),
+            ("x86_64-unknown-freebsd", None),
+            ("aarch64-unknown-none", None),
+        ] {
+            let expected = variant.map(|variant| variant(TargetTriple::parse(triple).unwrap()));
+            assert_eq!(os(triple), expected, "{}", triple);
+        }
+    }
+
+    #[test]
+    fn classifies_windows_and_wasm_flavors() {
+        let flags = |triple| {
+            let os = os(triple).unwrap();
+            (
+                os.is_windows_msvc(),
+                os.is_windows_gnu(),
+                os.is_emscripten(),
+                os.is_wasi(),
+            )
+        };
+        for (triple, expected) in [
+            ("x86_64-pc-windows-msvc", (true, false, false, false)),
+            ("i686-pc-windows-gnu", (false, true, false, false)),
+            ("aarch64-pc-windows-gnullvm", (false, true, false, false)),
+            ("wasm32-unknown-emscripten", (false, false, true, false)),
+            ("wasm32-wasip1", (false, false, false, true)),
+            ("wasm32-wasip2", (false, false, false, true)),
+            ("wasm32-unknown-unknown", (false, false, false, false)),
+            ("x86_64-unknown-linux-gnu", (false, false, false, false)),
+        ] {
+            assert_eq!(flags(triple), expected, "{}", triple);
+        }
+    }
+
+    #[test]
+    fn groups_apple_targets() {
+        for (triple, apple, simulator) in [
+            ("aarch64-apple-ios", true, false),
+            ("aarch64-apple-ios-sim", true, true),
+            ("x86_64-apple-ios", true, true),
+            ("aarch64-apple-ios-macabi", true, false),
+            ("x86_64-apple-ios-macabi", true, false),
+            ("x86_64-apple-darwin", true, false),
+            ("x86_64-apple-tvos", true, true),
+            ("aarch64-apple-visionos-sim", true, true),
+            ("aarch64-linux-android", false, false),
+            ("x86_64-pc-windows-msvc", false, false),
+        ] {
+            let os = os(triple).unwrap();
+            assert_eq!(
+                (os.is_apple(), os.is_simulator()),
+                (apple, simulator),
+                "{}",
+                triple
+            );
+        }
+    }
+
+    #[test]
+    fn reads_api_level_from_env() {
+        let env = BuildEnv::from_map([
+            ("TARGET", "aarch64-linux-android"),
+            ("ANDROID_PLATFORM", "android-24"),
+        ]);
+        let os = TargetOs::try_detect_from(&env).unwrap().unwrap();
+        assert_eq!(os.android_api_level(), Some(24));
+        assert_eq!(os.with_android_api_level(30).android_api_level(), Some(30));
+        let env = BuildEnv::from_map([("TARGET", "x86_64-unknown-freebsd")]);
+        assert_eq!(TargetOs::try_detect_from(&env).unwrap(), None);
+    }
+}
@@ -0,0 +1,585 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Arch {
+    Aarch64,
+    Arm64_32,
+    /// `arm`, `armv7`, `armv7s`, `armebv7r`, ...
+    Arm(String),
+    /// `thumbv6m`, `thumbv7em`, `thumbv7neon`, ...
+    Thumb(String),
+    /// `i386`, `i586`, `i686`, `x86`
+    X86(String),
+    X86_64,
+    X86_64h,
+    Wasm32,
+    Wasm64,
+    /// `riscv32i`, `riscv32imac`, ...
+    Riscv32(String),
+    /// `riscv64gc`, `riscv64imac`, ...
+    Riscv64(String),
+    PowerPc,
+    PowerPc64,
+    PowerPc64le,
+    S390x,
+    LoongArch64,
+    Sparc64,
+    /// `mips`, `mipsel`, `mips64`, `mips64el`, ...
+    Mips(String),
+    Other(String),
+}
+
+impl Arch {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "aarch64" | "arm64" => Self::Aarch64,
+            "arm64_32" => Self::Arm64_32,
+            "x86_64" => Self::X86_64,
+            "x86_64h" => Self::X86_64h,
+            "wasm32" => Self::Wasm32,
+            "wasm64" => Self::Wasm64,
+            "powerpc" => Self::PowerPc,
+            "powerpc64" => Self::PowerPc64,
+            "powerpc64le" => Self::PowerPc64le,
+            "s390x" => Self::S390x,
+            "loongarch64" => Self::LoongArch64,
+            "sparc64" => Self::Sparc64,
+            // `x86` is how `CARGO_CFG_TARGET_ARCH` spells all of these.
+            "i386" | "i586" | "i686" | "x86" => Self::X86(s.into()),
+            _ if s.starts_with("thumb") => Self::Thumb(s.into()),
+            _ if s.starts_with("arm") => Self::Arm(s.into()),
+            _ if s.starts_with("riscv32") => Self::Riscv32(s.into()),
+            _ if s.starts_with("riscv64") => Self::Riscv64(s.into()),
+            _ if s.starts_with("mips") => Self::Mips(s.into()),
+            _ => Self::Other(s.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Aarch64 => "aarch64",
+            Self::Arm64_32 => "arm64_32",
+            Self::X86_64 => "x86_64",
+            Self::X86_64h => "x86_64h",
+            Self::Wasm32 => "wasm32",
+            Self::Wasm64 => "wasm64",
+            Self::PowerPc => "powerpc",
+            Self::PowerPc64 => "powerpc64",
+            Self::PowerPc64le => "powerpc64le",
+            Self::S390x => "s390x",
+            Self::LoongArch64 => "loongarch64",
+            Self::Sparc64 => "sparc64",
+            Self::Arm(s)
+            | Self::Thumb(s)
+            | Self::X86(s)
+            | Self::Riscv32(s)
+            | Self::Riscv64(s)
+            | Self::Mips(s)
+            | Self::Other(s) => s,
+        }
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        matches!(self, Self::Wasm32 | Self::Wasm64)
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Vendor {
+    Apple,
+    Pc,
+    Unknown,
+    Other(String),
+}
+
+impl Vendor {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "apple" => Self::Apple,
+            "pc" => Self::Pc,
+            "unknown" => Self::Unknown,
+            _ => Self::Other(s.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Apple => "apple",
+            Self::Pc => "pc",
+            Self::Unknown => "unknown",
+            Self::Other(s) => s,
+        }
+    }
+}
+
+impl fmt::Display for Vendor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Os {
+    /// macOS; rustc spells it `darwin`.
+    Darwin,
+    Ios,
+    TvOs,
+    WatchOs,
+    VisionOs,
+    Linux,
+    /// Only `CARGO_CFG_TARGET_OS` says this; triples spell Android as `linux`
+    /// with an `android` environment.
+    Android,
+    Windows,
+    FreeBsd,
+    NetBsd,
+    OpenBsd,
+    Fuchsia,
+    Emscripten,
+    /// `wasi`, `wasip1`, `wasip2`, ...
+    Wasi(String),
+    Cuda,
+    None,
+    Unknown,
+    Other(String),
+}
+
+impl Os {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "darwin" | "macos" => Self::Darwin,
+            "ios" => Self::Ios,
+            "tvos" => Self::TvOs,
+            "watchos" => Self::WatchOs,
+            "visionos" => Self::VisionOs,
+            "linux" => Self::Linux,
+            "android" => Self::Android,
+            "windows" => Self::Windows,
+            "freebsd" => Self::FreeBsd,
+            "netbsd" => Self::NetBsd,
+            "openbsd" => Self::OpenBsd,
+            "fuchsia" => Self::Fuchsia,
+            "emscripten" => Self::Emscripten,
+            "cuda" => Self::Cuda,
+            "none" => Self::None,
+            "unknown" => Self::Unknown,
+            _ if s.starts_with("wasi") => Self::Wasi(s.into()),
+            _ => Self::Other(s.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Darwin => "darwin",
+            Self::Ios => "ios",
+            Self::TvOs => "tvos",
+            Self::WatchOs => "watchos",
+            Self::VisionOs => "visionos",
+            Self::Linux => "linux",
+            Self::Android => "android",
+            Self::Windows => "windows",
+            Self::FreeBsd => "freebsd",
+            Self::NetBsd => "netbsd",
+            Self::OpenBsd => "openbsd",
+            Self::Fuchsia => "fuchsia",
+            Self::Emscripten => "emscripten",
+            Self::Cuda => "cuda",
+            Self::None => "none",
+            Self::Unknown => "unknown",
+            Self::Wasi(s) | Self::Other(s) => s,
+        }
+    }
+
+    pub fn is_apple(&self) -> bool {
+        matches!(
+            self,
+            Self::Darwin | Self::Ios | Self::TvOs | Self::WatchOs | Self::VisionOs
+        )
+    }
+}
+
+impl fmt::Display for Os {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Env {
+    /// `gnu`, `gnueabihf`, `gnux32`, `gnuabi64`, ...
+    Gnu(String),
+    GnuLlvm,
+    /// `musl`, `musleabi`, `musleabihf`, ...
+    Musl(String),
+    Msvc,
+    /// `android`, `androideabi`
+    Android(String),
+    /// Apple simulator.
+    Sim,
+    /// Mac Catalyst.
+    MacAbi,
+    /// `eabi`, `eabihf`
+    Eabi(String),
+    Sgx,
+    Other(String),
+}
+
+impl Env {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "gnullvm" => Self::GnuLlvm,
+            "msvc" => Self::Msvc,
+            "sim" => Self::Sim,
+            "macabi" => Self::MacAbi,
+            "sgx" => Self::Sgx,
+            _ if s.starts_with("gnu") => Self::Gnu(s.into()),
+            _ if s.starts_with("musl") => Self::Musl(s.into()),
+            _ if s.starts_with("android") => Self::Android(s.into()),
+            _ if s.starts_with("eabi") => Self::Eabi(s.into()),
+            _ => Self::Other(s.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::GnuLlvm => "gnullvm",
+            Self::Msvc => "msvc",
+            Self::Sim => "sim",
+            Self::MacAbi => "macabi",
+            Self::Sgx => "sgx",
+            Self::Gnu(s) | Self::Musl(s) | Self::Android(s) | Self::Eabi(s) | Self::Other(s) => s,
+        }
+    }
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTripleError(String);
+
+impl fmt::Display for ParseTripleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid target triple {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTripleError {}
+
+/// A rustc target triple, split into its components.
+///
+/// rustc triples aren't uniform: most are `arch-vendor-os[-env]`, but some
+/// leave out the vendor (`aarch64-linux-android`, `thumbv7em-none-eabihf`) or
+/// everything after the OS (`wasm32-wasip1`). Missing vendors parse as
+/// [`Vendor::Unknown`], and the original string is kept so that `Display`
+/// round-trips.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TargetTriple {
+    triple: String,
+    pub arch: Arch,
+    pub vendor: Vendor,
+    pub os: Os,
+    pub env: Option<Env>,
+}
+
+impl TargetTriple {
+    pub fn parse(triple: &str) -> Result<Self, ParseTripleError> {
+        let err = || ParseTripleError(triple.into());
+        let parts = triple.split('-').collect::<Vec<_>>();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(err());
+        }
+        let (arch, vendor, os, env) = match parts.as_slice() {
+            [arch, os] => (*arch, "unknown", *os, None),
+            [arch, os, env] if is_vendorless_os(os) => (*arch, "unknown", *os, Some(*env)),
+            [arch, vendor, os] => (*arch, *vendor, *os, None),
+            [arch, vendor, os, env] => (*arch, *vendor, *os, Some(*env)),
+            _ => return Err(err()),
+        };
+        Ok(Self {
+            triple: triple.into(),
+            arch: Arch::parse(arch),
+            vendor: Vendor::parse(vendor),
+            os: Os::parse(os),
+            env: env.map(Env::parse),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.triple
+    }
+
+    pub fn is_apple(&self) -> bool {
+        self.vendor == Vendor::Apple || self.os.is_apple()
+    }
+
+    pub fn is_android(&self) -> bool {
+        matches!(self.env, Some(Env::Android(_)))
+    }
+
+    pub fn is_simulator(&self) -> bool {
+        self.env == Some(Env::Sim)
+    }
+
+    pub fn is_mac_catalyst(&self) -> bool {
+        self.env == Some(Env::MacAbi)
+    }
+}
+
+// These are the only OSes rustc names without a vendor in front.
+fn is_vendorless_os(os: &str) -> bool {
+    matches!(os, "linux" | "none" | "nuttx" | "rtems") || os.starts_with("wasi")
+}
+
+impl FromStr for TargetTriple {
+    type Err = ParseTripleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for TargetTriple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.triple)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `rustc --print target-list`, regenerated whenever the toolchain is bumped.
+    const RUSTC_TARGETS: &str = include_str!("../testdata/rustc-target-list.txt");
+
+    fn parse(triple: &str) -> TargetTriple {
+        TargetTriple::parse(triple).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn parses_every_rustc_target() {
+        for triple in RUSTC_TARGETS.lines() {
+            let parsed = parse(triple);
+            assert_eq!(parsed.to_string(), triple);
+            assert_eq!(parsed.as_str(), triple);
+            assert_eq!(triple.parse::<TargetTriple>().as_ref(), Ok(&parsed));
+
+            // Putting the components back together has to give the original
+            // triple, either with the vendor or without it.
+            let env = parsed.env.iter().map(Env::as_str);
+            let with_vendor = [
+                parsed.arch.as_str(),
+                parsed.vendor.as_str(),
+                parsed.os.as_str(),
+            ]
+            .into_iter()
+            .chain(env.clone())
+            .collect::<Vec<_>>()
+            .join("-");
+            let without_vendor = [parsed.arch.as_str(), parsed.os.as_str()]
+                .into_iter()
+                .chain(env)
+                .collect::<Vec<_>>()
+                .join("-");
+            assert!(
+                with_vendor == triple
+                    || (parsed.vendor == Vendor::Unknown && without_vendor == triple),
+                "{} parsed as {:?}",
+                triple,
+                parsed
+            );
+        }
+    }
+
+    #[test]
+    fn classifies_apple_targets_in_rustc_list() {
+        for triple in RUSTC_TARGETS.lines() {
+            let parsed = parse(triple);
+            assert_eq!(parsed.is_apple(), triple.contains("-apple-"), "{}", triple);
+            assert_eq!(
+                parsed.is_mac_catalyst(),
+                triple.ends_with("-macabi"),
+                "{}",
+                triple
+            );
+            assert_eq!(
+                parsed.is_simulator(),
+                triple.ends_with("-sim"),
+                "{}",
+                triple
+            );
+            assert_eq!(
+                parsed.is_android(),
+                triple.contains("-android"),
+                "{}",
+                triple
+            );
+        }
+    }
+
+    #[test]
+    fn parses_components() {
+        let cases = [
+            (
+                "aarch64-apple-ios-macabi",
+                Arch::Aarch64,
+                Vendor::Apple,
+                Os::Ios,
+                Some(Env::MacAbi),
+            ),
+            (
+                "x86_64-apple-tvos",
+                Arch::X86_64,
+                Vendor::Apple,
+                Os::TvOs,
+                None,
+            ),
+            (
+                "aarch64-apple-ios-sim",
+                Arch::Aarch64,
+                Vendor::Apple,
+                Os::Ios,
+                Some(Env::Sim),
+            ),
+            (
+                "aarch64-apple-darwin",
+                Arch::Aarch64,
+                Vendor::Apple,
+                Os::Darwin,
+                None,
+            ),
+            (
+                "arm64_32-apple-watchos",
+                Arch::Arm64_32,
+                Vendor::Apple,
+                Os::WatchOs,
+                None,
+            ),
+            (
+                "aarch64-apple-visionos",
+                Arch::Aarch64,
+                Vendor::Apple,
+                Os::VisionOs,
+                None,
+            ),
+            (
+                "aarch64-linux-android",
+                Arch::Aarch64,
+                Vendor::Unknown,
+                Os::Linux,
+                Some(Env::Android("android".into())),
+            ),
+            (
+                "armv7-linux-androideabi",
+                Arch::Arm("armv7".into()),
+                Vendor::Unknown,
+                Os::Linux,
+                Some(Env::Android("androideabi".into())),
+            ),
+            (
+                "wasm32-wasip1-threads",
+                Arch::Wasm32,
+                Vendor::Unknown,
+                Os::Wasi("wasip1".into()),
+                Some(Env::Other("threads".into())),
+            ),
+            (
+                "wasm32-wasip2",
+                Arch::Wasm32,
+                Vendor::Unknown,
+                Os::Wasi("wasip2".into()),
+                None,
+            ),
+            (
+                "wasm32-unknown-unknown",
+                Arch::Wasm32,
+                Vendor::Unknown,
+                Os::Unknown,
+                None,
+            ),
+            (
+                "wasm32-unknown-emscripten",
+                Arch::Wasm32,
+                Vendor::Unknown,
+                Os::Emscripten,
+                None,
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                Arch::X86_64,
+                Vendor::Unknown,
+                Os::Linux,
+                Some(Env::Gnu("gnu".into())),
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                Arch::X86_64,
+                Vendor::Pc,
+                Os::Windows,
+                Some(Env::Msvc),
+            ),
+            (
+                "x86_64-pc-windows-gnullvm",
+                Arch::X86_64,
+                Vendor::Pc,
+                Os::Windows,
+                Some(Env::GnuLlvm),
+            ),
+            (
+                "i686-unknown-linux-musl",
+                Arch::X86("i686".into()),
+                Vendor::Unknown,
+                Os::Linux,
+                Some(Env::Musl("musl".into())),
+            ),
+            (
+                "thumbv7em-none-eabihf",
+                Arch::Thumb("thumbv7em".into()),
+                Vendor::Unknown,
+                Os::None,
+                Some(Env::Eabi("eabihf".into())),
+            ),
+            (
+                "riscv64gc-unknown-linux-gnu",
+                Arch::Riscv64("riscv64gc".into()),
+                Vendor::Unknown,
+                Os::Linux,
+                Some(Env::Gnu("gnu".into())),
+            ),
+            (
+                "x86_64-fortanix-unknown-sgx",
+                Arch::X86_64,
+                Vendor::Other("fortanix".into()),
+                Os::Unknown,
+                Some(Env::Sgx),
+            ),
+        ];
+        for (triple, arch, vendor, os, env) in cases {
+            let parsed = parse(triple);
+            assert_eq!(parsed.arch, arch, "{}", triple);
+            assert_eq!(parsed.vendor, vendor, "{}", triple);
+            assert_eq!(parsed.os, os, "{}", triple);
+            assert_eq!(parsed.env, env, "{}", triple);
+            assert_eq!(parsed.to_string(), triple);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_triples() {
+        for triple in ["", "x86_64", "x86_64--linux", "-apple-ios", "a-b-c-d-e"] {
+            assert_eq!(
+                TargetTriple::parse(triple),
+                Err(ParseTripleError(triple.into()))
+            );
+        }
+    }
+}
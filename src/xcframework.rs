@@ -0,0 +1,252 @@
+use crate::{Arch, Error, LinkKind, Result, TargetOs};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+/// One entry in an xcframework's `AvailableLibraries`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XcFrameworkLibrary {
+    /// e.g. `ios-arm64_x86_64-simulator`, which is also the slice's
+    /// directory name.
+    pub identifier: String,
+    /// e.g. `Foo.framework` or `libfoo.a`, relative to the slice directory.
+    pub path: PathBuf,
+    /// e.g. `ios`, `macos`, `tvos`, `watchos` or `xros`.
+    pub platform: String,
+    /// `simulator`, `maccatalyst`, or nothing for devices.
+    pub platform_variant: Option<String>,
+    pub architectures: Vec<String>,
+}
+
+impl XcFrameworkLibrary {
+    pub fn kind(&self) -> Option<LinkKind> {
+        match self.path.extension().and_then(OsStr::to_str) {
+            Some("framework") => Some(LinkKind::Framework),
+            Some("a") => Some(LinkKind::Static),
+            Some("dylib") => Some(LinkKind::Dylib),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, os: &TargetOs) -> bool {
+        platform(os).is_some_and(|(platform, variant)| {
+            self.platform == platform
+                && self.platform_variant.as_deref() == variant
+                && self.architectures.iter().any(|arch| arch == self::arch(os))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XcFramework {
+    path: PathBuf,
+    libraries: Vec<XcFrameworkLibrary>,
+}
+
+impl XcFramework {
+    /// Reads `path/Info.plist`.
+    pub fn from_path(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let invalid = |reason: &str| Error::InvalidXcFramework {
+            path: path.clone(),
+            reason: reason.into(),
+        };
+        let info = plist::Value::from_file(path.join("Info.plist"))
+            .map_err(|err| invalid(&format!("couldn't read `Info.plist`: {}", err)))?;
+        let libraries = info
+            .as_dictionary()
+            .and_then(|info| info.get("AvailableLibraries"))
+            .and_then(plist::Value::as_array)
+            .ok_or_else(|| invalid("`Info.plist` has no `AvailableLibraries`"))?
+            .iter()
+            .map(|library| {
+                let library = library
+                    .as_dictionary()
+                    .ok_or_else(|| invalid("`AvailableLibraries` entries must be dictionaries"))?;
+                let string = |key| library.get(key).and_then(plist::Value::as_string);
+                let required = |key| {
+                    string(key)
+                        .map(String::from)
+                        .ok_or_else(|| invalid(&format!("a library is missing `{}`", key)))
+                };
+                Ok(XcFrameworkLibrary {
+                    identifier: required("LibraryIdentifier")?,
+                    path: required("LibraryPath")?.into(),
+                    platform: required("SupportedPlatform")?,
+                    platform_variant: string("SupportedPlatformVariant").map(String::from),
+                    architectures: library
+                        .get("SupportedArchitectures")
+                        .and_then(plist::Value::as_array)
+                        .map(|archs| {
+                            archs
+                                .iter()
+                                .filter_map(plist::Value::as_string)
+                                .map(String::from)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { path, libraries })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn libraries(&self) -> &[XcFrameworkLibrary] {
+        &self.libraries
+    }
+
+    /// The library built for `os`'s platform and arch, if there is one.
+    pub fn library_for(&self, os: &TargetOs) -> Option<&XcFrameworkLibrary> {
+        self.libraries.iter().find(|library| library.matches(os))
+    }
+
+    /// The directory containing `library`, i.e. its search path.
+    pub fn slice_dir(&self, library: &XcFrameworkLibrary) -> PathBuf {
+        self.path.join(&library.identifier)
+    }
+}
+
+// How xcframeworks spell each platform and variant.
+fn platform(os: &TargetOs) -> Option<(&'static str, Option<&'static str>)> {
+    let variant = os.is_simulator().then_some("simulator");
+    match os {
+        TargetOs::Ios(_) => Some(("ios", variant)),
+        TargetOs::MacCatalyst(_) => Some(("ios", Some("maccatalyst"))),
+        TargetOs::MacOs(_) => Some(("macos", None)),
+        TargetOs::TvOs(_) => Some(("tvos", variant)),
+        TargetOs::WatchOs(_) => Some(("watchos", variant)),
+        TargetOs::VisionOs(_) => Some(("xros", variant)),
+        TargetOs::Android(..) | TargetOs::Linux(_) | TargetOs::Windows(_) | TargetOs::Wasm(_) => {
+            None
+        }
+    }
+}
+
+// Apple's arch names, which are mostly the same as rustc's.
+fn arch(os: &TargetOs) -> &str {
+    match &os.triple().arch {
+        Arch::Aarch64 => "arm64",
+        Arch::X86(_) => "i386",
+        arch => arch.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LinkItem, LinkScanner, TargetTriple};
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/Foo.xcframework")
+    }
+
+    fn os(triple: &str) -> TargetOs {
+        TargetOs::from_triple(TargetTriple::parse(triple).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_info_plist() {
+        let xcframework = XcFramework::from_path(fixture()).unwrap();
+        assert_eq!(xcframework.libraries().len(), 4);
+        assert_eq!(
+            xcframework.libraries()[1],
+            XcFrameworkLibrary {
+                identifier: "ios-arm64_x86_64-simulator".into(),
+                path: "Foo.framework".into(),
+                platform: "ios".into(),
+                platform_variant: Some("simulator".into()),
+                architectures: vec!["arm64".into(), "x86_64".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn picks_slices() {
+        let xcframework = XcFramework::from_path(fixture()).unwrap();
+        for (triple, identifier) in [
+            ("aarch64-apple-ios", Some("ios-arm64")),
+            ("aarch64-apple-ios-sim", Some("ios-arm64_x86_64-simulator")),
+            ("x86_64-apple-ios", Some("ios-arm64_x86_64-simulator")),
+            (
+                "aarch64-apple-ios-macabi",
+                Some("ios-arm64_x86_64-maccatalyst"),
+            ),
+            (
+                "x86_64-apple-ios-macabi",
+                Some("ios-arm64_x86_64-maccatalyst"),
+            ),
+            ("aarch64-apple-darwin", Some("macos-arm64_x86_64")),
+            ("x86_64-apple-darwin", Some("macos-arm64_x86_64")),
+            ("aarch64-apple-tvos", None),
+            ("aarch64-linux-android", None),
+        ] {
+            assert_eq!(
+                xcframework
+                    .library_for(&os(triple))
+                    .map(|library| library.identifier.as_str()),
+                identifier,
+                "{}",
+                triple
+            );
+        }
+    }
+
+    #[test]
+    fn links_the_matching_slice() {
+        let discover = |triple| {
+            LinkScanner::new(fixture().parent().unwrap())
+                .glob("*.xcframework")
+                .target(os(triple))
+                .try_discover()
+        };
+        assert_eq!(
+            discover("aarch64-apple-ios-sim").unwrap(),
+            [LinkItem::new(
+                LinkKind::Framework,
+                "Foo",
+                fixture().join("ios-arm64_x86_64-simulator/Foo.framework")
+            )
+            .unwrap()]
+        );
+        assert_eq!(
+            discover("aarch64-apple-darwin").unwrap(),
+            [LinkItem::new(
+                LinkKind::Static,
+                "Foo",
+                fixture().join("macos-arm64_x86_64/libFoo.a")
+            )
+            .unwrap()]
+        );
+        // Non-Apple targets don't look inside xcframeworks at all.
+        assert_eq!(discover("aarch64-linux-android").unwrap(), []);
+        match discover("aarch64-apple-tvos") {
+            Err(Error::InvalidXcFramework { path, reason }) => {
+                assert_eq!(path, fixture());
+                assert_eq!(reason, "no slice for `aarch64-apple-tvos`");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_info_plists() {
+        let dir = tempfile::tempdir().unwrap();
+        let reason = |path: &Path| match XcFramework::from_path(path) {
+            Err(Error::InvalidXcFramework { reason, .. }) => reason,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert!(reason(dir.path()).starts_with("couldn't read `Info.plist`"));
+        plist::Value::Dictionary(Default::default())
+            .to_file_xml(dir.path().join("Info.plist"))
+            .unwrap();
+        assert_eq!(
+            reason(dir.path()),
+            "`Info.plist` has no `AvailableLibraries`"
+        );
+    }
+}